@@ -9,9 +9,9 @@ fn main() {
 fn make_rss() -> Result<String, ()> {
     // Make format dependent on CLI parameter.
     let format = if std::env::args().nth(1).is_some_and(|s| s == "--pretty") {
-        ogrim::Format::Pretty { indentation: "  " }
+        ogrim::Format::pretty().max_width(80)
     } else {
-        ogrim::Format::Terse
+        ogrim::Format::terse()
     };
 
     let buf = xml!(
@@ -6,13 +6,23 @@ use proc_macro2::{TokenStream, Ident};
 pub(crate) struct Input {
     pub(crate) buffer: Option<TokenStream>,
     pub(crate) format: Option<TokenStream>,
+    pub(crate) char_policy: Option<TokenStream>,
+    pub(crate) bom: Option<TokenStream>,
     pub(crate) prolog: Option<Prolog>,
     pub(crate) root: Element,
 }
 
+#[derive(Debug)]
+pub(crate) struct MatchInput {
+    pub(crate) input: TokenStream,
+    pub(crate) root: Element,
+    pub(crate) body: Option<TokenStream>,
+}
+
 #[derive(Debug)]
 pub(crate) struct Prolog {
     pub(crate) version: String,
+    pub(crate) encoding: Option<String>,
     pub(crate) standalone: Option<String>,
 }
 
@@ -22,12 +32,23 @@ pub(crate) struct Element {
     pub(crate) attrs: Vec<Attr>,
     pub(crate) children: Vec<Child>,
     pub(crate) empty: bool,
+    /// Set via `#[preserve_space]` before the element.
+    pub(crate) preserve_space: bool,
 }
 
 #[derive(Debug)]
 pub(crate) enum Attr {
     Single(Name, AttrValue),
+    /// `name?={expr}`, omitted if `expr` is `None` or `false`.
+    Optional(Name, TokenStream),
     Fill(TokenStream),
+    /// `if cond { ... } else { ... }`. An `else if` is an `If` as the only
+    /// attribute in `otherwise`.
+    If {
+        cond: TokenStream,
+        then: Vec<Attr>,
+        otherwise: Vec<Attr>,
+    },
 }
 
 #[derive(Debug)]
@@ -45,6 +66,8 @@ pub(crate) enum Child {
 pub(crate) enum AttrValue {
     Literal(String),
     Expr(TokenStream),
+    /// `{..iter}`, joined with spaces.
+    List(TokenStream),
 }
 
 #[derive(Debug)]
@@ -16,15 +16,40 @@ pub(crate) fn emit(input: ast::Input) -> Result<TokenStream, Error> {
             "1.1" => quote! { ogrim::Version::V1_1 },
             other => return Err(err!("invalid version '{other}'")),
         };
-        let standalone = match prolog.standalone {
+        // Encoding names are case-insensitive.
+        let encoding = match prolog.encoding.as_deref().map(|e| e.to_ascii_uppercase()).as_deref() {
+            None | Some("UTF-8") => quote! { ogrim::Encoding::Utf8 },
+            Some("UTF-16" | "UTF-16LE") => quote! { ogrim::Encoding::Utf16Le },
+            Some("UTF-16BE") => quote! { ogrim::Encoding::Utf16Be },
+            Some("ISO-8859-1") => quote! { ogrim::Encoding::Latin1 },
+            Some("WINDOWS-1252") => quote! { ogrim::Encoding::Windows1252 },
+            Some(_) => return Err(err!(
+                "unsupported encoding '{}', supported are 'UTF-8', 'UTF-16', \
+                    'UTF-16LE', 'UTF-16BE', 'ISO-8859-1' and 'windows-1252'",
+                prolog.encoding.unwrap(),
+            )),
+        };
+        let standalone = match prolog.standalone.as_deref() {
             None => quote! { None },
-            Some(v) => quote! { Some(#v) },
+            Some("yes") => quote! { Some(true) },
+            Some("no") => quote! { Some(false) },
+            Some(other) => return Err(err!("invalid standalone value '{other}'")),
         };
-        let format = input.format.unwrap_or(quote! { ogrim::Format::Terse });
+        let format = input.format.unwrap_or(quote! { ogrim::Format::terse() });
+        let char_policy = input.char_policy
+            .unwrap_or(quote! { ogrim::CharPolicy::Replace });
+        let bom = input.bom.unwrap_or(quote! { false });
 
 
         quote! {
-            let mut buf = ogrim::Document::new(#version, #standalone, #format);
+            let mut buf = ogrim::Document::new(
+                #version,
+                #encoding,
+                #standalone,
+                #format,
+                #char_policy,
+                #bom,
+            );
         }
     };
     let ret = if input.buffer.is_some() { quote!{} } else { quote! { buf } };
@@ -42,33 +67,48 @@ pub(crate) fn emit(input: ast::Input) -> Result<TokenStream, Error> {
 }
 
 
+pub(crate) fn emit_tree(input: ast::Input) -> Result<TokenStream, Error> {
+    if input.buffer.is_some()
+        || input.format.is_some()
+        || input.char_policy.is_some()
+        || input.bom.is_some()
+    {
+        return Err(err!("`xml_tree!` only accepts an element: no buffer or meta attributes"));
+    }
+    if input.prolog.is_some() {
+        return Err(err!("`xml_tree!` does not accept an XML prolog"));
+    }
+
+    let root = emit_element(&input.root);
+    Ok(quote! {
+        {
+            let mut buf = ogrim::tree::Builder::new();
+            #root
+            buf.finish()
+        }
+    })
+}
+
+
 fn emit_element(elem: &ast::Element) -> TokenStream {
     let name = &elem.name;
     let mut out = quote! {
         buf.open_tag(#name);
     };
+    out.extend(emit_attrs(&elem.attrs));
 
+    if elem.preserve_space {
+        out.extend(quote! {
+            buf.preserve_space();
+        });
+    }
 
-    for attr in &elem.attrs {
-        match attr {
-            ast::Attr::Single(name, value) => {
-                let (span, v) = match value {
-                    ast::AttrValue::Literal(s) => (Span::call_site(), quote! { #s }),
-                    ast::AttrValue::Expr(e) => (
-                        span_of_tokenstream(&e),
-                        quote! { (#e) },
-                    ),
-                };
-                out.extend(quote_spanned!{span=>
-                    buf.attr(#name, &#v);
-                });
-            }
-            ast::Attr::Fill(expr) => {
-                out.extend(quote! {
-                    buf.attrs(#expr);
-                });
-            }
-        }
+    let has_text = elem.children.iter()
+        .any(|c| matches!(c, ast::Child::Text(_) | ast::Child::TextExpr(_)));
+    if has_text {
+        out.extend(quote! {
+            buf.mixed_content();
+        });
     }
 
     if elem.empty {
@@ -103,6 +143,61 @@ fn emit_element(elem: &ast::Element) -> TokenStream {
     out
 }
 
+fn emit_attrs(attrs: &[ast::Attr]) -> TokenStream {
+    let mut out = TokenStream::new();
+    for attr in attrs {
+        match attr {
+            ast::Attr::Single(name, ast::AttrValue::Literal(s)) => {
+                out.extend(quote! {
+                    buf.attr(#name, &#s);
+                });
+            }
+            ast::Attr::Single(name, ast::AttrValue::Expr(e)) => {
+                // Uses `AttrValue` if implemented and `Display` otherwise, see
+                // `attr.rs` in the main crate.
+                let span = span_of_tokenstream(e);
+                out.extend(quote_spanned!{span=>
+                    {
+                        #[allow(unused_imports)]
+                        use ogrim::{__ViaAttrValue as _, __ViaDisplay as _};
+                        buf.typed_attr(#name, &(&&ogrim::__AttrWrap(&(#e))).attr_value());
+                    }
+                });
+            }
+            ast::Attr::Single(name, ast::AttrValue::List(e)) => {
+                let span = span_of_tokenstream(e);
+                out.extend(quote_spanned!{span=>
+                    buf.attr(#name, &ogrim::join(#e, " "));
+                });
+            }
+            ast::Attr::Optional(name, e) => {
+                let span = span_of_tokenstream(e);
+                // Like above, `AttrValue` is preferred over `Display`.
+                out.extend(quote_spanned!{span=>
+                    {
+                        #[allow(unused_imports)]
+                        use ogrim::{__ViaOptAttrValue as _, __ViaOptionalAttr as _};
+                        buf.opt_attr(#name, (&&ogrim::__AttrWrap(&(#e))).optional_value());
+                    }
+                });
+            }
+            ast::Attr::Fill(expr) => {
+                out.extend(quote! {
+                    buf.attrs(#expr);
+                });
+            }
+            ast::Attr::If { cond, then, otherwise } => {
+                let then = emit_attrs(then);
+                let otherwise = emit_attrs(otherwise);
+                out.extend(quote! {
+                    if #cond { #then } else { #otherwise }
+                });
+            }
+        }
+    }
+    out
+}
+
 impl quote::ToTokens for ast::Name {
     fn to_tokens(&self, tokens: &mut TokenStream) {
         let s = &self.0;
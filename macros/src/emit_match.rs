@@ -0,0 +1,140 @@
+use proc_macro2::{Ident, Span, TokenStream, TokenTree};
+use quote::{quote, quote_spanned};
+
+use crate::{ast, err::{err, Error}};
+
+
+pub(crate) fn emit(input: ast::MatchInput) -> Result<TokenStream, Error> {
+    let mut bindings = vec![];
+    let steps = emit_element(&input.root, &mut bindings)?;
+    let input_expr = input.input;
+    let body = input.body.unwrap_or(quote! { () });
+
+    // All identifiers we introduce use `mixed_site` hygiene, so that they
+    // cannot clash with the user's bindings.
+    Ok(quote_spanned! {Span::mixed_site()=>
+        {
+            // Bound separately so that temporaries in the input expression
+            // (e.g. `s.as_str()`) live until the end of the block.
+            let input_owner = &#input_expr;
+            let input: &str = ::core::convert::AsRef::<str>::as_ref(input_owner);
+            let result = (|| -> ::core::result::Result<_, ogrim::ParseError> {
+                let mut matcher = ogrim::__Matcher::new(input);
+                #steps
+                matcher.finish()?;
+                ::core::result::Result::Ok((#(#bindings,)*))
+            })();
+            match result {
+                ::core::result::Result::Ok((#(#bindings,)*)) => {
+                    ::core::result::Result::<_, ogrim::ParseError>::Ok(#body)
+                }
+                ::core::result::Result::Err(e) => ::core::result::Result::Err(e),
+            }
+        }
+    })
+}
+
+fn emit_element(elem: &ast::Element, bindings: &mut Vec<Ident>) -> Result<TokenStream, Error> {
+    let name = &elem.name;
+    let mut out = quote_spanned! {Span::mixed_site()=>
+        matcher.element(#name)?;
+    };
+
+    for attr in &elem.attrs {
+        match attr {
+            ast::Attr::Single(name, ast::AttrValue::Literal(s)) => {
+                out.extend(quote_spanned! {Span::mixed_site()=>
+                    matcher.expect_attr(#name, #s)?;
+                });
+            }
+            ast::Attr::Single(name, ast::AttrValue::Expr(e)) => {
+                let value = quote_spanned! {Span::mixed_site()=> matcher.attr(#name)? };
+                out.extend(emit_binding(e, value, bindings)?);
+            }
+            ast::Attr::Single(_, ast::AttrValue::List(_)) => {
+                return Err(err!("`{{..iter}}` attribute values are not supported in patterns"));
+            }
+            ast::Attr::Optional(..) => {
+                return Err(err!("`name?={{..}}` attributes are not supported in patterns"));
+            }
+            ast::Attr::If { .. } => {
+                return Err(err!("`if` in start tags is not supported in patterns"));
+            }
+            ast::Attr::Fill(_) => {
+                return Err(err!("`{{..}}` attributes are not supported in patterns"));
+            }
+        }
+    }
+
+    let has_text = elem.children.iter()
+        .any(|c| matches!(c, ast::Child::Text(_) | ast::Child::TextExpr(_)));
+    if has_text && elem.children.len() > 1 {
+        return Err(err!(
+            "element '{}' in pattern: text patterns must be the only child of an element",
+            elem.name.0,
+        ));
+    }
+
+    match elem.children.first() {
+        Some(ast::Child::Text(s)) => out.extend(quote_spanned! {Span::mixed_site()=>
+            matcher.expect_text(#s)?;
+        }),
+        Some(ast::Child::TextExpr(e)) => {
+            let value = quote_spanned! {Span::mixed_site()=> matcher.text()? };
+            out.extend(emit_binding(e, value, bindings)?);
+        }
+        _ => {
+            for child in &elem.children {
+                match child {
+                    ast::Child::Element(child) => out.extend(emit_element(child, bindings)?),
+                    ast::Child::Closure { arg, .. } => {
+                        return Err(err!(@arg.span(), "`{{|..| ..}}` is not supported in patterns"));
+                    }
+                    ast::Child::Text(_) | ast::Child::TextExpr(_) => unreachable!(),
+                }
+            }
+            out.extend(quote_spanned! {Span::mixed_site()=>
+                matcher.end()?;
+            });
+        }
+    }
+
+    Ok(out)
+}
+
+/// Emits a `let` statement for a binding `{name}` or `{name: Type}`, where
+/// `value` evaluates to the string value.
+fn emit_binding(
+    binding: &TokenStream,
+    value: TokenStream,
+    bindings: &mut Vec<Ident>,
+) -> Result<TokenStream, Error> {
+    let mut tokens = binding.clone().into_iter();
+    let ident = match tokens.next() {
+        Some(TokenTree::Ident(i)) => i,
+        other => {
+            let span = other.map(|tt| tt.span()).unwrap_or(Span::call_site());
+            return Err(err!(@span, "expected binding: `{{name}}` or `{{name: Type}}`"));
+        }
+    };
+    let ty = match tokens.next() {
+        None => quote! { ::std::string::String },
+        Some(TokenTree::Punct(p)) if p.as_char() == ':' => {
+            let ty = TokenStream::from_iter(tokens);
+            if ty.is_empty() {
+                return Err(err!(@p.span(), "expected type after ':'"));
+            }
+            ty
+        }
+        Some(other) => {
+            return Err(err!(@other.span(), "expected binding: `{{name}}` or `{{name: Type}}`"));
+        }
+    };
+
+    let name = ident.to_string();
+    bindings.push(ident.clone());
+    Ok(quote_spanned! {Span::mixed_site()=>
+        let value = #value;
+        let #ident: #ty = matcher.convert(#name, value)?;
+    })
+}
@@ -5,6 +5,7 @@ use proc_macro2::TokenStream as TokenStream2;
 
 mod ast;
 mod emit;
+mod emit_match;
 mod err;
 mod parse;
 
@@ -15,7 +16,27 @@ pub fn xml(input: TokenStream) -> TokenStream {
     run(input.into()).unwrap_or_else(|err| err.to_compile_error()).into()
 }
 
+#[proc_macro]
+pub fn xml_tree(input: TokenStream) -> TokenStream {
+    run_tree(input.into()).unwrap_or_else(|err| err.to_compile_error()).into()
+}
+
+#[proc_macro]
+pub fn xml_match(input: TokenStream) -> TokenStream {
+    run_match(input.into()).unwrap_or_else(|err| err.to_compile_error()).into()
+}
+
 fn run(input: TokenStream2) -> Result<TokenStream2, Error> {
     let input = ast::Input::parse_input(input)?;
     emit::emit(input)
 }
+
+fn run_tree(input: TokenStream2) -> Result<TokenStream2, Error> {
+    let input = ast::Input::parse_input(input)?;
+    emit::emit_tree(input)
+}
+
+fn run_match(input: TokenStream2) -> Result<TokenStream2, Error> {
+    let input = ast::MatchInput::parse_input(input)?;
+    emit_match::emit(input)
+}
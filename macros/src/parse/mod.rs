@@ -25,6 +25,15 @@ impl ast::Input {
     }
 }
 
+impl ast::MatchInput {
+    pub(crate) fn parse_input(tokens: TokenStream) -> Result<Self, Error> {
+        let mut buf = ParseBuf::from_stream(tokens);
+        let out = ast::MatchInput::parse(&mut buf)?;
+        buf.expect_eof()?;
+        Ok(out)
+    }
+}
+
 fn is_punct(tt: &TokenTree, c: char) -> bool {
     matches!(tt, TokenTree::Punct(p) if p.as_char() == c)
 }
@@ -33,6 +42,8 @@ impl Parse for ast::Input {
     fn parse(buf: &mut ParseBuf) -> Result<Self, Error> {
         let mut buffer = None;
         let mut format = None;
+        let mut char_policy = None;
+        let mut bom = None;
 
         loop {
             match buf.curr()? {
@@ -47,6 +58,14 @@ impl Parse for ast::Input {
                             let _ = inner.expect_punct('=')?;
                             format = Some(inner.collect_rest());
                         }
+                        "char_policy" => {
+                            let _ = inner.expect_punct('=')?;
+                            char_policy = Some(inner.collect_rest());
+                        }
+                        "bom" => {
+                            let _ = inner.expect_punct('=')?;
+                            bom = Some(inner.collect_rest());
+                        }
                         other => return Err(err!(
                             @key.span(),
                             "unsupported global attribute '{other}'",
@@ -77,18 +96,52 @@ impl Parse for ast::Input {
             None
         };
 
+        let preserve_space = parse_element_meta(buf)?;
         buf.expect_punct('<')?;
-        let root = buf.parse()?;
+        let mut root: ast::Element = buf.parse()?;
+        root.preserve_space = preserve_space;
 
         Ok(Self {
             buffer,
             format,
+            char_policy,
+            bom,
             prolog,
             root,
         })
     }
 }
 
+impl Parse for ast::MatchInput {
+    fn parse(buf: &mut ParseBuf) -> Result<Self, Error> {
+        let mut input = vec![];
+        while !is_punct(buf.curr()?, ',') {
+            input.push(buf.bump()?);
+        }
+        let _ = buf.bump()?; // Eat `,`
+
+        buf.expect_punct('<')?;
+        let root = buf.parse()?;
+
+        let body = if buf.curr().is_ok() {
+            let p = buf.expect_punct('=')?;
+            if p.spacing() != Spacing::Joint {
+                return Err(err!(@p.span(), "expected '=>'"));
+            }
+            buf.expect_punct('>')?;
+            Some(TokenStream::from_iter(std::iter::from_fn(|| buf.bump().ok())))
+        } else {
+            None
+        };
+
+        Ok(Self {
+            input: TokenStream::from_iter(input),
+            root,
+            body,
+        })
+    }
+}
+
 // Assumes `<` is already eaten.
 impl Parse for ast::Prolog {
     fn parse(buf: &mut ParseBuf) -> Result<Self, Error> {
@@ -128,12 +181,7 @@ impl Parse for ast::Prolog {
         buf.expect_punct('?')?;
         buf.expect_punct('>')?;
 
-        if encoding.as_ref().is_some_and(|enc| enc != "UTF-8") {
-            // TODO: span would be nice
-            return Err(err!("only encoding 'UTF-8' is allowed"));
-        };
-
-        Ok(Self { version, standalone })
+        Ok(Self { version, encoding, standalone })
     }
 }
 
@@ -156,24 +204,10 @@ impl Parse for ast::Element {
                         attrs,
                         children: vec![],
                         empty: true,
+                        preserve_space: false,
                     })
                 }
-                TokenTree::Group(g) if g.delimiter() == Delimiter::Brace => {
-                    let g = buf.expect_group(Delimiter::Brace)?;
-                    let mut inner = ParseBuf::from_group(g);
-                    let p = inner.expect_punct('.')?;
-                    if p.spacing() == Spacing::Alone {
-                        return Err(err!(@p.span(), "expected '..' but found single '.'"));
-                    }
-                    inner.expect_punct('.')?;
-                    attrs.push(ast::Attr::Fill(inner.collect_rest()));
-                }
-                _ => {
-                    let name = buf.parse()?;
-                    buf.expect_punct('=')?;
-                    let value = buf.parse()?;
-                    attrs.push(ast::Attr::Single(name, value));
-                }
+                _ => attrs.push(buf.parse()?),
             }
         }
 
@@ -199,8 +233,138 @@ impl Parse for ast::Element {
         }
 
 
-        Ok(Self { name, attrs, children, empty: false })
+        Ok(Self { name, attrs, children, empty: false, preserve_space: false })
+    }
+}
+
+impl Parse for ast::Attr {
+    fn parse(buf: &mut ParseBuf) -> Result<Self, Error> {
+        match buf.curr()? {
+            TokenTree::Group(g) if g.delimiter() == Delimiter::Brace => {
+                let g = buf.expect_group(Delimiter::Brace)?;
+                let mut inner = ParseBuf::from_group(g);
+
+                // Shorthand `{name}` for `name={name}`.
+                if let (Ok(TokenTree::Ident(ident)), Err(_)) = (inner.curr(), inner.next()) {
+                    let s = ident.to_string();
+                    let name = s.strip_prefix("r#").unwrap_or(&s).to_owned();
+                    if !is_name(&name) {
+                        return Err(err!(@ident.span(),
+                            "'{name}' is not a valid XML name",
+                        ));
+                    }
+                    let value = ast::AttrValue::Expr(inner.collect_rest());
+                    return Ok(Self::Single(ast::Name(name), value));
+                }
+
+                let p = inner.expect_punct('.')?;
+                if p.spacing() == Spacing::Alone {
+                    return Err(err!(@p.span(), "expected '..' but found single '.'"));
+                }
+                inner.expect_punct('.')?;
+                Ok(Self::Fill(inner.collect_rest()))
+            }
+
+            // `if cond { ... }`, unless `if` is the name of an attribute.
+            TokenTree::Ident(i) if i.to_string() == "if" && !continues_attr_name(buf.next()?) => {
+                let _ = buf.bump();
+
+                // Like in Rust, the condition ends at the first brace group.
+                let mut cond = vec![];
+                while !matches!(buf.curr()?, TokenTree::Group(g) if g.delimiter() == Delimiter::Brace) {
+                    cond.push(buf.bump()?);
+                }
+                let then = parse_attr_block(buf)?;
+
+                let is_else = buf.curr()
+                    .is_ok_and(|tt| matches!(tt, TokenTree::Ident(i) if i.to_string() == "else"))
+                    && !buf.next().is_ok_and(continues_attr_name);
+                let otherwise = if !is_else {
+                    vec![]
+                } else {
+                    let _ = buf.bump();
+                    match buf.curr()? {
+                        TokenTree::Ident(i) if i.to_string() == "if" => vec![buf.parse()?],
+                        _ => parse_attr_block(buf)?,
+                    }
+                };
+
+                Ok(Self::If {
+                    cond: TokenStream::from_iter(cond),
+                    then,
+                    otherwise,
+                })
+            }
+
+            _ => {
+                let name = buf.parse()?;
+                if is_punct(buf.curr()?, '?') {
+                    let _ = buf.bump();
+                    buf.expect_punct('=')?;
+                    match buf.bump()? {
+                        TokenTree::Group(g) if g.delimiter() == Delimiter::Brace => {
+                            Ok(Self::Optional(name, g.stream()))
+                        }
+                        other => Err(err!(
+                            @other.span(),
+                            "expected {{...}} with an `Option` or `bool` after `?=`",
+                        )),
+                    }
+                } else {
+                    buf.expect_punct('=')?;
+                    let value = buf.parse()?;
+                    Ok(Self::Single(name, value))
+                }
+            }
+        }
+    }
+}
+
+/// Returns whether `tt`, following an identifier, ends or continues an
+/// attribute name, i.e. whether that identifier is (the start of) an attribute
+/// name and not a keyword. A single `:` continues a name (e.g. `else:foo`),
+/// while `::` starts a path in a condition.
+fn continues_attr_name(tt: &TokenTree) -> bool {
+    match tt {
+        TokenTree::Punct(p) => match p.as_char() {
+            '=' | '?' | '-' | '.' => true,
+            ':' => p.spacing() == Spacing::Alone,
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+/// Parses a `{ ... }` group of attributes, as used by `if`.
+fn parse_attr_block(buf: &mut ParseBuf) -> Result<Vec<ast::Attr>, Error> {
+    let g = buf.expect_group(Delimiter::Brace)?;
+    let mut inner = ParseBuf::from_group(g);
+    let mut attrs = vec![];
+    while inner.curr().is_ok() {
+        attrs.push(inner.parse()?);
+    }
+    Ok(attrs)
+}
+
+/// Parses meta attributes in front of an element, e.g. `#[preserve_space]`.
+/// Returns whether `preserve_space` was specified.
+fn parse_element_meta(buf: &mut ParseBuf) -> Result<bool, Error> {
+    let mut preserve_space = false;
+    while is_punct(buf.curr()?, '#') {
+        let _ = buf.bump();
+        let g = buf.expect_group(Delimiter::Bracket)?;
+        let mut inner = ParseBuf::from_group(g);
+        let key = inner.expect_ident()?;
+        match key.to_string().as_str() {
+            "preserve_space" => preserve_space = true,
+            other => return Err(err!(
+                @key.span(),
+                "unsupported element attribute '{other}'",
+            )),
+        }
+        inner.expect_eof()?;
     }
+    Ok(preserve_space)
 }
 
 impl Parse for ast::Name {
@@ -334,6 +498,14 @@ impl Parse for ast::Name {
 
 impl Parse for ast::Child {
     fn parse(buf: &mut ParseBuf) -> Result<Self, Error> {
+        if is_punct(buf.curr()?, '#') {
+            let preserve_space = parse_element_meta(buf)?;
+            buf.expect_punct('<')?;
+            let mut elem: ast::Element = buf.parse()?;
+            elem.preserve_space = preserve_space;
+            return Ok(Self::Element(elem));
+        }
+
         match buf.bump()? {
             TokenTree::Literal(l) => {
                 let slit = StringLit::try_from(&l)
@@ -360,7 +532,7 @@ impl Parse for ast::Child {
             }
             other => Err(err!(
                 @other.span(),
-                "expected element child: string literal, {{...}} or '<'",
+                "expected element child: string literal, {{...}}, '<' or '#[...]'",
             )),
         }
     }
@@ -377,7 +549,18 @@ impl Parse for ast::AttrValue {
                 Ok(Self::Literal(v))
             }
             TokenTree::Group(g) if g.delimiter() == Delimiter::Brace => {
-                Ok(Self::Expr(g.stream()))
+                let mut inner = ParseBuf::from_group(g.clone());
+                let is_list = inner.curr().is_ok_and(|tt| matches!(
+                    tt,
+                    TokenTree::Punct(p) if p.as_char() == '.' && p.spacing() == Spacing::Joint,
+                )) && inner.next().is_ok_and(|tt| is_punct(tt, '.'));
+                if is_list {
+                    let _ = inner.bump();
+                    let _ = inner.bump();
+                    Ok(Self::List(inner.collect_rest()))
+                } else {
+                    Ok(Self::Expr(g.stream()))
+                }
             }
             other => Err(err!(
                 @other.span(),
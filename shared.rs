@@ -4,6 +4,9 @@
 // it's already bad enough that two crates are required for all of this. So
 // screw it, I just `include!` this code in both code bases.
 
+/// Checks whether `s` is a valid XML name. These rules are the ones from XML
+/// 1.1, which XML 1.0 adopted in its fifth edition. So they are the same for
+/// both versions.
 fn is_name(s: &str) -> bool {
     let mut chars = s.chars();
     let Some(first) = chars.next() else {
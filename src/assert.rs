@@ -0,0 +1,211 @@
+use std::fmt::{self, Write};
+
+use crate::{Event, Reader};
+
+
+
+/// Asserts that two XML documents are equal, ignoring formatting differences.
+///
+/// Both arguments have to implement `AsRef<str>` (e.g. `&str`, `String` or
+/// [`Document`][crate::Document]) and must be well-formed XML. The following
+/// differences are ignored:
+/// - whitespace-only text nodes (e.g. indentation),
+/// - attribute order and whether `"` or `'` is used to quote values,
+/// - `<foo/>` vs `<foo></foo>`,
+/// - how characters are encoded, e.g. `&gt;`, `&#62;` and `>` are all equal,
+///   and so are CDATA sections and escaped text,
+/// - the XML declaration and document type declaration.
+///
+/// Names are compared literally, namespace prefixes are not resolved. On
+/// failure, this panics with a diff of both documents. Like with
+/// `assert_eq!`, you can pass a custom message after the two arguments.
+///
+/// ```
+/// use ogrim::{xml, assert_xml_eq, Format};
+///
+/// let doc = xml!(
+///     #[format = Format::pretty()]
+///     <?xml version="1.0" ?>
+///     <foo a="1" b={"x > y"}>
+///         <bar></bar>
+///         <baz name="Peter" />
+///     </foo>
+/// );
+///
+/// assert_xml_eq!(doc, r#"<foo b='x &#62; y' a="1"><bar/><baz name='Peter'></baz></foo>"#);
+/// ```
+#[macro_export]
+macro_rules! assert_xml_eq {
+    ($left:expr, $right:expr $(,)?) => {
+        $crate::__assert_xml_eq(
+            ::core::convert::AsRef::<str>::as_ref(&$left),
+            ::core::convert::AsRef::<str>::as_ref(&$right),
+            ::core::option::Option::None,
+        )
+    };
+    ($left:expr, $right:expr, $($arg:tt)+) => {
+        $crate::__assert_xml_eq(
+            ::core::convert::AsRef::<str>::as_ref(&$left),
+            ::core::convert::AsRef::<str>::as_ref(&$right),
+            ::core::option::Option::Some(::core::format_args!($($arg)+)),
+        )
+    };
+}
+
+#[doc(hidden)]
+#[track_caller]
+pub fn assert_xml_eq(left: &str, right: &str, msg: Option<fmt::Arguments<'_>>) {
+    let parse = |side: &str, input: &str| match Node::parse_document(input) {
+        Ok(nodes) => nodes,
+        Err(e) => panic!("{side} side of `assert_xml_eq!` is not well-formed XML: {e}"),
+    };
+    let left = parse("left", left);
+    let right = parse("right", right);
+    if left == right {
+        return;
+    }
+
+    let mut left_str = String::new();
+    let mut right_str = String::new();
+    left.iter().for_each(|n| n.render(&mut left_str, 0));
+    right.iter().for_each(|n| n.render(&mut right_str, 0));
+    let diff = line_diff(&left_str, &right_str);
+    match msg {
+        Some(msg) => panic!("assertion `left == right` failed: {msg}\nXML diff (-left +right):\n{diff}"),
+        None => panic!("assertion `left == right` failed\nXML diff (-left +right):\n{diff}"),
+    }
+}
+
+
+/// A normalized XML node, used for comparison.
+#[derive(Debug, PartialEq)]
+enum Node {
+    Element {
+        name: String,
+        /// Sorted by name.
+        attrs: Vec<(String, String)>,
+        children: Vec<Node>,
+    },
+    Text(String),
+    Comment(String),
+    Pi(String, String),
+}
+
+impl Node {
+    /// Parses all top-level nodes of a document.
+    fn parse_document(input: &str) -> Result<Vec<Node>, crate::ParseError> {
+        let mut reader = Reader::new(input);
+
+        // Stack of open elements. The bottom one is a pseudo element holding
+        // the top-level nodes.
+        let mut stack = vec![Node::Element { name: String::new(), attrs: vec![], children: vec![] }];
+        let mut text = String::new();
+
+        while let Some(event) = reader.next_event()? {
+            if !matches!(event, Event::Text(_) | Event::Attr { .. }) {
+                let Some(Node::Element { children, .. }) = stack.last_mut() else { unreachable!() };
+                flush_text(&mut text, children);
+            }
+
+            match event {
+                Event::StartTag(name) => stack.push(Node::Element {
+                    name: name.into(),
+                    attrs: vec![],
+                    children: vec![],
+                }),
+                Event::Attr { name, value } => {
+                    let Some(Node::Element { attrs, .. }) = stack.last_mut() else { unreachable!() };
+                    let pos = attrs.partition_point(|(n, _)| n.as_str() < name);
+                    attrs.insert(pos, (name.into(), value.unescape().into_owned()));
+                }
+                Event::Text(s) => text.push_str(&s.unescape()),
+                Event::End(_) => {
+                    let elem = stack.pop().unwrap();
+                    let Some(Node::Element { children, .. }) = stack.last_mut() else { unreachable!() };
+                    children.push(elem);
+                }
+                Event::Comment(s) => {
+                    let Some(Node::Element { children, .. }) = stack.last_mut() else { unreachable!() };
+                    children.push(Node::Comment(s.into()));
+                }
+                Event::Pi { target, data } => {
+                    let Some(Node::Element { children, .. }) = stack.last_mut() else { unreachable!() };
+                    children.push(Node::Pi(target.into(), data.into()));
+                }
+            }
+        }
+
+        let Some(Node::Element { children, .. }) = stack.pop() else { unreachable!() };
+        Ok(children)
+    }
+
+    /// Renders this node in a format similar to the `xml!` syntax, one node
+    /// per line.
+    fn render(&self, out: &mut String, depth: usize) {
+        let indent = "    ".repeat(depth);
+        match self {
+            Node::Element { name, attrs, children } => {
+                write!(out, "{indent}<{name}").unwrap();
+                for (name, value) in attrs {
+                    write!(out, " {name}={value:?}").unwrap();
+                }
+                if children.is_empty() {
+                    out.push_str(" />\n");
+                } else {
+                    out.push_str(">\n");
+                    children.iter().for_each(|c| c.render(out, depth + 1));
+                    writeln!(out, "{indent}</{name}>").unwrap();
+                }
+            }
+            Node::Text(s) => writeln!(out, "{indent}{s:?}").unwrap(),
+            Node::Comment(s) => writeln!(out, "{indent}<!--{s}-->").unwrap(),
+            Node::Pi(target, data) => writeln!(out, "{indent}<?{target} {data}?>").unwrap(),
+        }
+    }
+}
+
+/// Adds `text` as text node to `children` unless it only consists of
+/// whitespace. Clears `text`.
+fn flush_text(text: &mut String, children: &mut Vec<Node>) {
+    if !text.trim_matches([' ', '\t', '\r', '\n']).is_empty() {
+        children.push(Node::Text(text.clone()));
+    }
+    text.clear();
+}
+
+/// Returns a line based diff of both strings, with `-` and `+` marking lines
+/// only in `left` or `right`, respectively.
+fn line_diff(left: &str, right: &str) -> String {
+    let left = left.lines().collect::<Vec<_>>();
+    let right = right.lines().collect::<Vec<_>>();
+
+    // Classic longest common subsequence table: `lcs[i][j]` is the LCS length
+    // of `left[i..]` and `right[j..]`.
+    let mut lcs = vec![vec![0; right.len() + 1]; left.len() + 1];
+    for i in (0..left.len()).rev() {
+        for j in (0..right.len()).rev() {
+            lcs[i][j] = if left[i] == right[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut out = String::new();
+    let (mut i, mut j) = (0, 0);
+    while i < left.len() || j < right.len() {
+        if i < left.len() && j < right.len() && left[i] == right[j] {
+            writeln!(out, "  {}", left[i]).unwrap();
+            i += 1;
+            j += 1;
+        } else if i < left.len() && (j == right.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            writeln!(out, "- {}", left[i]).unwrap();
+            i += 1;
+        } else {
+            writeln!(out, "+ {}", right[j]).unwrap();
+            j += 1;
+        }
+    }
+    out
+}
@@ -0,0 +1,282 @@
+use std::fmt;
+
+
+/// Values usable with the optional attribute shorthand `name?={value}` of
+/// `xml!`. Implemented for `bool` and `Option<T>`. For `Option<T>` with
+/// `T: AttrValue`, the macro uses `ViaOptAttrValue` instead, see below.
+pub trait OptionalAttr {
+    fn optional_value(&self) -> OptionalValue<'_>;
+}
+
+/// What to write for an optional attribute.
+pub enum OptionalValue<'a> {
+    /// Leave the attribute out.
+    Omit,
+    /// A boolean attribute, written with the value configured in the format.
+    Flag,
+    Value(&'a dyn AttrValue),
+    /// A value not implementing `AttrValue`, written via `Display`.
+    Display(&'a dyn fmt::Display),
+}
+
+impl OptionalAttr for bool {
+    fn optional_value(&self) -> OptionalValue<'_> {
+        if *self { OptionalValue::Flag } else { OptionalValue::Omit }
+    }
+}
+
+impl<T: fmt::Display> OptionalAttr for Option<T> {
+    fn optional_value(&self) -> OptionalValue<'_> {
+        match self {
+            Some(v) => OptionalValue::Display(v),
+            None => OptionalValue::Omit,
+        }
+    }
+}
+
+impl<T: OptionalAttr + ?Sized> OptionalAttr for &T {
+    fn optional_value(&self) -> OptionalValue<'_> {
+        (**self).optional_value()
+    }
+}
+
+
+/// Values of interpolated attributes like `foo={value}` in [`xml!`].
+///
+/// Attribute values are written via this trait if implemented, and via
+/// [`fmt::Display`] otherwise. The implementations in this crate produce
+/// values valid for the corresponding XML Schema types, e.g. `INF` for
+/// infinite floats, and omit the attribute for `None`.
+///
+/// ```
+/// use ogrim::xml;
+///
+/// let doc = xml!(
+///     <?xml version="1.0" ?>
+///     <point x={f64::INFINITY} y={-0.5f32} z={None::<u8>} visible={true} />
+/// );
+///
+/// assert_eq!(doc.as_str(), concat!(
+///     r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+///     r#"<point x="INF" y="-0.5" visible="true"/>"#,
+/// ));
+/// ```
+///
+/// Implementing it for your own types is useful when their `Display` output
+/// is meant for humans, not as attribute value.
+///
+/// [`xml!`]: crate::xml
+pub trait AttrValue {
+    /// Writes the unescaped value.
+    fn fmt_attr(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result;
+
+    /// Returns whether to leave out the attribute entirely. Default: `false`.
+    fn is_omitted(&self) -> bool {
+        false
+    }
+}
+
+/// Forwards to `Display`. Used for types that are valid as is.
+macro_rules! impl_via_display {
+    ($($t:ty),*) => {
+        $(
+            impl AttrValue for $t {
+                fn fmt_attr(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                    fmt::Display::fmt(self, f)
+                }
+            }
+        )*
+    };
+}
+
+impl_via_display!(
+    bool, char, str, String,
+    i8, i16, i32, i64, i128, isize,
+    u8, u16, u32, u64, u128, usize
+);
+
+/// `Display` of floats already matches `xsd:double` (without exponent), except
+/// for infinity.
+macro_rules! impl_float {
+    ($($t:ty),*) => {
+        $(
+            impl AttrValue for $t {
+                fn fmt_attr(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                    match *self {
+                        <$t>::INFINITY => f.write_str("INF"),
+                        <$t>::NEG_INFINITY => f.write_str("-INF"),
+                        v => fmt::Display::fmt(&v, f),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_float!(f32, f64);
+
+impl<T: AttrValue> AttrValue for Option<T> {
+    fn fmt_attr(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Some(v) => v.fmt_attr(f),
+            None => Ok(()),
+        }
+    }
+
+    fn is_omitted(&self) -> bool {
+        self.as_ref().map_or(true, |v| v.is_omitted())
+    }
+}
+
+impl<T: AttrValue + ?Sized> AttrValue for &T {
+    fn fmt_attr(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (**self).fmt_attr(f)
+    }
+
+    fn is_omitted(&self) -> bool {
+        (**self).is_omitted()
+    }
+}
+
+/// Makes an `AttrValue` usable where `Display` is expected.
+pub(crate) struct Formatted<'a>(pub(crate) &'a dyn AttrValue);
+
+impl fmt::Display for Formatted<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt_attr(f)
+    }
+}
+
+/// `Display` fallback for values not implementing `AttrValue`.
+pub struct DisplayValue<'a>(&'a dyn fmt::Display);
+
+impl AttrValue for DisplayValue<'_> {
+    fn fmt_attr(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+// Autoref specialization: the macro calls `(&&Wrap(&value)).attr_value()`.
+// Method resolution first tries `ViaAttrValue`, which takes one more
+// reference, and only falls back to `ViaDisplay` if `T: AttrValue` does not
+// hold.
+
+/// Wrapper for interpolated attribute values, used by `xml!`.
+pub struct Wrap<'a, T>(pub &'a T);
+
+pub trait ViaAttrValue<'a> {
+    fn attr_value(&self) -> &'a dyn AttrValue;
+}
+
+impl<'a, T: AttrValue> ViaAttrValue<'a> for &Wrap<'a, T> {
+    fn attr_value(&self) -> &'a dyn AttrValue {
+        self.0
+    }
+}
+
+pub trait ViaDisplay<'a> {
+    fn attr_value(&self) -> DisplayValue<'a>;
+}
+
+impl<'a, T: fmt::Display> ViaDisplay<'a> for Wrap<'a, T> {
+    fn attr_value(&self) -> DisplayValue<'a> {
+        DisplayValue(self.0)
+    }
+}
+
+// The same for optional attributes `name?={value}`, which the macro writes
+// via `(&&Wrap(&value)).optional_value()`: `Option<T>` (or a reference to
+// it) uses `AttrValue` if `T` implements it, everything else `OptionalAttr`.
+
+pub trait ViaOptAttrValue<'a> {
+    fn optional_value(&self) -> OptionalValue<'a>;
+}
+
+impl<'a, T: AttrValue> ViaOptAttrValue<'a> for &Wrap<'a, Option<T>> {
+    fn optional_value(&self) -> OptionalValue<'a> {
+        match self.0 {
+            Some(v) => OptionalValue::Value(v),
+            None => OptionalValue::Omit,
+        }
+    }
+}
+
+impl<'a, T: AttrValue> ViaOptAttrValue<'a> for &Wrap<'a, &Option<T>> {
+    fn optional_value(&self) -> OptionalValue<'a> {
+        match self.0 {
+            Some(v) => OptionalValue::Value(v),
+            None => OptionalValue::Omit,
+        }
+    }
+}
+
+pub trait ViaOptionalAttr<'a> {
+    fn optional_value(&self) -> OptionalValue<'a>;
+}
+
+impl<'a, T: OptionalAttr> ViaOptionalAttr<'a> for Wrap<'a, T> {
+    fn optional_value(&self) -> OptionalValue<'a> {
+        self.0.optional_value()
+    }
+}
+
+
+/// Joins the items of `iter` with `sep` when formatted, without building an
+/// intermediate `String`. Useful for list-valued attributes like `class` or
+/// `points`, for which [`xml!`] also has the shorthand `attr={..iter}`,
+/// joining with a space.
+///
+/// Formatting iterates over a clone of `iter`, so the result can be formatted
+/// any number of times. To avoid copying a collection, pass it by reference
+/// (e.g. `&vec`) or pass an iterator that is cheap to clone.
+///
+/// ```
+/// use ogrim::{xml, join};
+///
+/// let classes = vec![String::from("card"), String::from("active")];
+/// let points = [(0, 0), (10, 5)].iter().map(|(x, y)| join([x, y], ","));
+/// let doc = xml!(
+///     <?xml version="1.0" ?>
+///     <svg class={..&classes}><polyline points={join(points, " ")} /></svg>
+/// );
+///
+/// assert_eq!(doc.as_str(), concat!(
+///     r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+///     r#"<svg class="card active"><polyline points="0,0 10,5"/></svg>"#,
+/// ));
+/// ```
+///
+/// [`xml!`]: crate::xml
+pub fn join<I, S>(iter: I, sep: S) -> Join<I::IntoIter, S>
+where
+    I: IntoIterator,
+    I::IntoIter: Clone,
+    I::Item: fmt::Display,
+    S: fmt::Display,
+{
+    Join { iter: iter.into_iter(), sep }
+}
+
+/// Returned by [`join`].
+#[derive(Debug, Clone)]
+pub struct Join<I, S> {
+    iter: I,
+    sep: S,
+}
+
+impl<I, S> fmt::Display for Join<I, S>
+where
+    I: Iterator + Clone,
+    I::Item: fmt::Display,
+    S: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, item) in self.iter.clone().enumerate() {
+            if i > 0 {
+                self.sep.fmt(f)?;
+            }
+            item.fmt(f)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,315 @@
+use std::{borrow::Cow, fmt::Write};
+
+use crate::{Event, ParseError, Reader};
+
+
+
+/// Namespace URI bound to the `xml` prefix.
+const XML_NS: &str = "http://www.w3.org/XML/1998/namespace";
+
+/// Converts XML documents into their canonical form, as defined by
+/// [Canonical XML 1.0][c14n] or [Exclusive XML Canonicalization 1.0][exc].
+///
+/// Canonical XML is required to sign documents (e.g. with XML Signature), as
+/// two documents that only differ in irrelevant details have the same
+/// canonical form. Among other things, canonicalization removes the XML and
+/// document type declaration, writes empty elements as start-end pairs,
+/// sorts attributes, removes superfluous namespace declarations, replaces
+/// references and CDATA sections and escapes characters in a fixed way.
+///
+/// As this works on strings, pass it the output of [`xml!`][crate::xml]. The
+/// whole document is canonicalized, document subsets are not supported.
+/// DTDs are skipped, so default attributes and entities declared in them are
+/// not supported.
+///
+/// ```
+/// use ogrim::{xml, C14n};
+///
+/// let doc = xml!(
+///     <?xml version="1.0" ?>
+///     <n0:local xmlns:n0="foo:bar" xmlns:n3="ftp://example.org">
+///         <n1:elem2 xml:lang="en" xmlns:n1="http://example.net" b="2" a="1">
+///             <n3:stuff xmlns:n3="ftp://example.org" />
+///         </n1:elem2>
+///     </n0:local>
+/// );
+///
+/// assert_eq!(C14n::inclusive().canonicalize(doc.as_str()).unwrap(), concat!(
+///     r#"<n0:local xmlns:n0="foo:bar" xmlns:n3="ftp://example.org">"#,
+///     r#"<n1:elem2 xmlns:n1="http://example.net" a="1" b="2" xml:lang="en">"#,
+///     r#"<n3:stuff></n3:stuff></n1:elem2></n0:local>"#,
+/// ));
+/// assert_eq!(C14n::exclusive().canonicalize(doc.as_str()).unwrap(), concat!(
+///     r#"<n0:local xmlns:n0="foo:bar">"#,
+///     r#"<n1:elem2 xmlns:n1="http://example.net" a="1" b="2" xml:lang="en">"#,
+///     r#"<n3:stuff xmlns:n3="ftp://example.org"></n3:stuff></n1:elem2></n0:local>"#,
+/// ));
+/// ```
+///
+/// [c14n]: https://www.w3.org/TR/2001/REC-xml-c14n-20010315
+/// [exc]: https://www.w3.org/TR/2002/REC-xml-exc-c14n-20020718/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct C14n {
+    exclusive: bool,
+    comments: bool,
+    inclusive_prefixes: Vec<String>,
+}
+
+impl C14n {
+    /// Canonical XML 1.0 (without comments): all namespace declarations in
+    /// scope are written on the root element and wherever they change.
+    pub fn inclusive() -> Self {
+        Self {
+            exclusive: false,
+            comments: false,
+            inclusive_prefixes: Vec::new(),
+        }
+    }
+
+    /// Exclusive XML Canonicalization 1.0 (without comments): namespace
+    /// declarations are only written on elements using them. This is
+    /// usually what you want for signing parts of a document, e.g. in SAML
+    /// or SOAP.
+    pub fn exclusive() -> Self {
+        Self { exclusive: true, ..Self::inclusive() }
+    }
+
+    /// Sets whether comments are kept (the "WithComments" variants).
+    pub fn with_comments(mut self, comments: bool) -> Self {
+        self.comments = comments;
+        self
+    }
+
+    /// Sets the "InclusiveNamespaces PrefixList" of exclusive
+    /// canonicalization: namespace declarations of these prefixes are
+    /// treated like in inclusive canonicalization. Use `#default` for the
+    /// default namespace.
+    pub fn inclusive_prefixes<I, S>(mut self, prefixes: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.inclusive_prefixes = prefixes.into_iter().map(|p| {
+            let p = p.into();
+            if p == "#default" { String::new() } else { p }
+        }).collect();
+        self
+    }
+
+    /// Returns the canonical form of `input`, which has to be a well-formed
+    /// XML document.
+    pub fn canonicalize(&self, input: &str) -> Result<String, ParseError> {
+        let mut reader = Reader::new(input);
+        let mut out = String::with_capacity(input.len());
+
+        // Declared namespaces as `(prefix, uri)`, with the default namespace
+        // having an empty prefix. `in_scope` contains the declarations of
+        // all open elements, `rendered` the ones we have written. The
+        // `*_len` stacks store their lengths before each open element.
+        let mut in_scope = Vec::<(&str, Cow<'_, str>)>::new();
+        let mut rendered = Vec::<(&str, String)>::new();
+        let mut scope_len = Vec::new();
+
+        let mut depth = 0;
+        let mut after_root = false;
+        let mut event = reader.next_event()?;
+        while let Some(e) = event.take() {
+            match e {
+                Event::StartTag(name) => {
+                    let pos = reader.event_pos();
+                    let mut attrs = Vec::new();
+                    let mut decls = Vec::new();
+                    loop {
+                        match reader.next_event()? {
+                            Some(Event::Attr { name, value }) => {
+                                if name == "xmlns" {
+                                    decls.push(("", value));
+                                } else if let Some(prefix) = name.strip_prefix("xmlns:") {
+                                    decls.push((prefix, value));
+                                } else {
+                                    attrs.push((name, value.unescape()));
+                                }
+                            }
+                            other => {
+                                event = other;
+                                break;
+                            }
+                        }
+                    }
+
+                    scope_len.push((in_scope.len(), rendered.len()));
+                    in_scope.extend(decls.into_iter().map(|(p, v)| (p, v.unescape())));
+
+                    // Find out which namespace declarations to write: all in
+                    // scope for inclusive, only the visibly utilized ones for
+                    // exclusive canonicalization. They are only written if
+                    // not already rendered on an output ancestor.
+                    let mut prefixes = Vec::new();
+                    if self.exclusive {
+                        prefixes.push(prefix_of(name));
+                        prefixes.extend(attrs.iter().map(|(n, _)| prefix_of(n)).filter(|p| !p.is_empty()));
+                        prefixes.extend(self.inclusive_prefixes.iter().map(|p| p.as_str()));
+                    } else {
+                        prefixes.extend(in_scope.iter().map(|(p, _)| *p));
+                        prefixes.push("");
+                    }
+                    prefixes.sort_unstable();
+                    prefixes.dedup();
+
+                    let mut ns_out = Vec::new();
+                    for prefix in prefixes {
+                        let uri = lookup(&in_scope, prefix);
+                        let current = lookup(&rendered, prefix);
+                        match uri {
+                            _ if prefix == "xml" => {}
+                            // For the default namespace, no declaration and
+                            // `xmlns=""` are the same.
+                            _ if prefix.is_empty() => {
+                                if uri.unwrap_or("") != current.unwrap_or("") {
+                                    ns_out.push((prefix, uri.unwrap_or("")));
+                                }
+                            }
+                            Some(uri) if current != Some(uri) => ns_out.push((prefix, uri)),
+                            Some(_) => {}
+                            None if self.inclusive_prefixes.iter().any(|p| p == prefix) => {}
+                            None => return Err(reader.error_at(pos, format!(
+                                "namespace prefix '{prefix}' is not declared",
+                            ))),
+                        }
+                    }
+
+                    // Sort attributes by namespace URI first, then local name.
+                    let mut sorted = Vec::with_capacity(attrs.len());
+                    for (name, value) in attrs {
+                        let (uri, local) = match name.split_once(':') {
+                            None => ("", name),
+                            Some(("xml", local)) => (XML_NS, local),
+                            Some((prefix, local)) => match lookup(&in_scope, prefix) {
+                                Some(uri) => (uri, local),
+                                None => return Err(reader.error_at(pos, format!(
+                                    "namespace prefix '{prefix}' is not declared",
+                                ))),
+                            },
+                        };
+                        sorted.push(((uri, local), name, value));
+                    }
+                    sorted.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+
+                    write!(out, "<{name}").unwrap();
+                    for &(prefix, uri) in &ns_out {
+                        if prefix.is_empty() {
+                            out.push_str(" xmlns=\"");
+                        } else {
+                            write!(out, " xmlns:{prefix}=\"").unwrap();
+                        }
+                        escape_attr(uri, &mut out);
+                        out.push('"');
+                    }
+                    for (_, name, value) in sorted {
+                        write!(out, " {name}=\"").unwrap();
+                        escape_attr(&value, &mut out);
+                        out.push('"');
+                    }
+                    out.push('>');
+
+                    rendered.extend(ns_out.into_iter().map(|(p, uri)| (p, uri.to_owned())));
+                    depth += 1;
+                    continue;
+                }
+                Event::Attr { .. } => unreachable!(),
+                Event::Text(text) => escape_text(&text.unescape(), &mut out),
+                Event::End(name) => {
+                    write!(out, "</{name}>").unwrap();
+                    let (scope, rend) = scope_len.pop().unwrap();
+                    in_scope.truncate(scope);
+                    rendered.truncate(rend);
+                    depth -= 1;
+                    after_root = depth == 0;
+                }
+                Event::Comment(_) if !self.comments => {}
+                Event::Comment(comment) => {
+                    outside_root(depth, after_root, &mut out, |out| {
+                        out.push_str("<!--");
+                        normalize_newlines(comment, out);
+                        out.push_str("-->");
+                    });
+                }
+                Event::Pi { target, data } => {
+                    outside_root(depth, after_root, &mut out, |out| {
+                        write!(out, "<?{target}").unwrap();
+                        if !data.is_empty() {
+                            out.push(' ');
+                            normalize_newlines(data, out);
+                        }
+                        out.push_str("?>");
+                    });
+                }
+            }
+            event = reader.next_event()?;
+        }
+
+        Ok(out)
+    }
+}
+
+/// Returns the URI bound to `prefix` in the given declarations.
+fn lookup<'a>(ns: &'a [(&str, impl AsRef<str>)], prefix: &str) -> Option<&'a str> {
+    ns.iter().rev().find(|(p, _)| *p == prefix).map(|(_, uri)| uri.as_ref())
+}
+
+/// Writes a comment or PI via `f`, adding newlines to separate it from the
+/// root element if it is outside of it.
+fn outside_root(depth: u32, after_root: bool, out: &mut String, f: impl FnOnce(&mut String)) {
+    if depth == 0 && after_root {
+        out.push('\n');
+    }
+    f(out);
+    if depth == 0 && !after_root {
+        out.push('\n');
+    }
+}
+
+/// Returns the namespace prefix of the given element or attribute name,
+/// which is empty for unprefixed names.
+fn prefix_of(name: &str) -> &str {
+    name.split_once(':').map_or("", |(prefix, _)| prefix)
+}
+
+fn escape_text(s: &str, out: &mut String) {
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '\r' => out.push_str("&#xD;"),
+            c => out.push(c),
+        }
+    }
+}
+
+fn escape_attr(s: &str, out: &mut String) {
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '"' => out.push_str("&quot;"),
+            '\t' => out.push_str("&#x9;"),
+            '\n' => out.push_str("&#xA;"),
+            '\r' => out.push_str("&#xD;"),
+            c => out.push(c),
+        }
+    }
+}
+
+/// Pushes `s` to `out`, replacing `\r\n` and `\r` with `\n`.
+fn normalize_newlines(s: &str, out: &mut String) {
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\r' {
+            chars.next_if_eq(&'\n');
+            out.push('\n');
+        } else {
+            out.push(c);
+        }
+    }
+}
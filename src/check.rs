@@ -0,0 +1,29 @@
+use crate::{ParseError, Reader};
+
+
+
+/// Checks whether `input` is a well-formed XML document.
+///
+/// This checks that tags are balanced, names are valid, there is exactly one
+/// root element, attributes are unique per element, `<` and `&` are
+/// properly escaped, references are valid and that only allowed characters
+/// are used. It is not a validating parser: DTDs are skipped and entities
+/// declared in them are not supported, so any entity reference other than the
+/// five predefined ones (`&lt;` and friends) is an error. Namespaces are not
+/// checked either.
+///
+/// Output of [`xml!`][crate::xml] should always pass this check, so this is
+/// mostly useful for tests or for checking XML from other sources.
+///
+/// ```
+/// assert!(ogrim::check(r#"<?xml version="1.0"?><foo bar="3">"x"</foo>"#).is_ok());
+///
+/// let err = ogrim::check("<foo>\n  <bar></baz>\n</foo>").unwrap_err();
+/// assert_eq!((err.line(), err.column()), (2, 8));
+/// assert_eq!(err.to_string(), "2:8: end tag 'baz' does not match start tag 'bar'");
+/// ```
+pub fn check(input: &str) -> Result<(), ParseError> {
+    let mut reader = Reader::new(input);
+    while reader.next_event()?.is_some() {}
+    Ok(())
+}
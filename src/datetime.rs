@@ -0,0 +1,256 @@
+//! Formatting of dates and times as commonly needed in XML documents.
+//!
+//! The wrapper types in this module can be interpolated in [`xml!`] (as text
+//! or attribute value) and write the wrapped value in the respective format,
+//! without allocating. They are implemented for the types of the `chrono`
+//! and `time` crates, if the crate feature of the same name is enabled.
+//!
+//! ```
+//! # #[cfg(feature = "chrono")] {
+//! use chrono::{FixedOffset, TimeZone};
+//! use ogrim::{xml, datetime::{Rfc3339, Rfc822}};
+//!
+//! let date = FixedOffset::east_opt(2 * 3600).unwrap()
+//!     .with_ymd_and_hms(2002, 9, 7, 9, 42, 31).unwrap();
+//! let doc = xml!(
+//!     <?xml version="1.0" ?>
+//!     <item>
+//!         <pubDate>{Rfc822(date)}</pubDate>
+//!         <updated>{Rfc3339(date)}</updated>
+//!     </item>
+//! );
+//!
+//! assert_eq!(doc.as_str(), concat!(
+//!     r#"<?xml version="1.0" encoding="UTF-8"?><item>"#,
+//!     "<pubDate>Sat, 07 Sep 2002 09:42:31 +0200</pubDate>",
+//!     "<updated>2002-09-07T09:42:31+02:00</updated>",
+//!     "</item>",
+//! ));
+//! # }
+//! ```
+//!
+//! [`xml!`]: crate::xml
+
+use std::fmt;
+
+
+/// Formats a date with time and offset as specified by [RFC 822][rfc] (with
+/// four digit years as per RFC 1123), e.g. `Sat, 07 Sep 2002 09:42:31 +0200`.
+/// This is the format of dates in RSS.
+///
+/// Implemented for `chrono::DateTime` and `time::OffsetDateTime`.
+///
+/// [rfc]: https://www.rfc-editor.org/rfc/rfc822#section-5
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rfc822<T>(pub T);
+
+/// Formats a date with time and offset as specified by [RFC 3339][rfc], e.g.
+/// `2002-09-07T09:42:31+02:00`, or with `Z` for UTC. Fractional seconds are
+/// written with 3, 6 or 9 digits if not zero. This is the format of dates in
+/// Atom and sitemaps, and also valid `xsd:dateTime`.
+///
+/// Implemented for `chrono::DateTime` and `time::OffsetDateTime`.
+///
+/// [rfc]: https://www.rfc-editor.org/rfc/rfc3339#section-5.6
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rfc3339<T>(pub T);
+
+/// Formats a date as `xsd:date`, e.g. `2002-09-07`. For dates with offset,
+/// the offset is appended, e.g. `2002-09-07+02:00`.
+///
+/// Implemented for `chrono::NaiveDate`, `chrono::DateTime`, `time::Date` and
+/// `time::OffsetDateTime`.
+///
+/// ```
+/// # #[cfg(feature = "time")] {
+/// use ogrim::{xml, datetime::XsdDate};
+/// use time::{Date, Month};
+///
+/// let date = Date::from_calendar_date(2024, Month::February, 29).unwrap();
+/// let doc = xml!(<?xml version="1.0" ?><birthday date={XsdDate(date)} />);
+/// assert!(doc.as_str().ends_with(r#"<birthday date="2024-02-29"/>"#));
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct XsdDate<T>(pub T);
+
+
+/// The components of a date and time, which all formats are written from.
+struct Parts {
+    year: i32,
+    /// 1 to 12.
+    month: u8,
+    day: u8,
+    /// Days since Monday, 0 to 6.
+    weekday: u8,
+    hour: u8,
+    minute: u8,
+    second: u8,
+    nanosecond: u32,
+    /// Offset from UTC in seconds.
+    offset: i32,
+}
+
+impl Parts {
+    fn write_rfc822(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const DAYS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+        const MONTHS: [&str; 12] = [
+            "Jan", "Feb", "Mar", "Apr", "May", "Jun",
+            "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+        ];
+
+        let sign = if self.offset < 0 { '-' } else { '+' };
+        let offset = self.offset.unsigned_abs() / 60;
+        write!(
+            f,
+            "{}, {:02} {} {:04} {:02}:{:02}:{:02} {sign}{:02}{:02}",
+            DAYS[self.weekday as usize],
+            self.day,
+            MONTHS[self.month as usize - 1],
+            self.year,
+            self.hour,
+            self.minute,
+            self.second,
+            offset / 60,
+            offset % 60,
+        )
+    }
+
+    fn write_rfc3339(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write_date(f)?;
+        write!(f, "T{:02}:{:02}:{:02}", self.hour, self.minute, self.second)?;
+        let ns = self.nanosecond;
+        if ns % 1_000_000 == 0 && ns != 0 {
+            write!(f, ".{:03}", ns / 1_000_000)?;
+        } else if ns % 1_000 == 0 && ns != 0 {
+            write!(f, ".{:06}", ns / 1_000)?;
+        } else if ns != 0 {
+            write!(f, ".{ns:09}")?;
+        }
+        self.write_offset(f)
+    }
+
+    fn write_date(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.year < 0 {
+            f.write_str("-")?;
+        }
+        write!(f, "{:04}-{:02}-{:02}", self.year.unsigned_abs(), self.month, self.day)
+    }
+
+    /// Writes `Z` for UTC and `+hh:mm` otherwise.
+    fn write_offset(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.offset == 0 {
+            return f.write_str("Z");
+        }
+        let sign = if self.offset < 0 { '-' } else { '+' };
+        let offset = self.offset.unsigned_abs() / 60;
+        write!(f, "{sign}{:02}:{:02}", offset / 60, offset % 60)
+    }
+}
+
+
+#[cfg(feature = "chrono")]
+mod chrono_impls {
+    use std::fmt;
+    use chrono::{Datelike, DateTime, NaiveDate, Offset, TimeZone, Timelike};
+    use super::{Parts, Rfc3339, Rfc822, XsdDate};
+
+    fn parts<Tz: TimeZone>(dt: &DateTime<Tz>) -> Parts {
+        Parts {
+            year: dt.year(),
+            month: dt.month() as u8,
+            day: dt.day() as u8,
+            weekday: dt.weekday().num_days_from_monday() as u8,
+            hour: dt.hour() as u8,
+            minute: dt.minute() as u8,
+            // Leap seconds are represented as nanoseconds >= 1s in chrono.
+            second: (dt.second() + dt.nanosecond() / 1_000_000_000) as u8,
+            nanosecond: dt.nanosecond() % 1_000_000_000,
+            offset: dt.offset().fix().local_minus_utc(),
+        }
+    }
+
+    impl<Tz: TimeZone> fmt::Display for Rfc822<DateTime<Tz>> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            parts(&self.0).write_rfc822(f)
+        }
+    }
+
+    impl<Tz: TimeZone> fmt::Display for Rfc3339<DateTime<Tz>> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            parts(&self.0).write_rfc3339(f)
+        }
+    }
+
+    impl<Tz: TimeZone> fmt::Display for XsdDate<DateTime<Tz>> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            let parts = parts(&self.0);
+            parts.write_date(f)?;
+            parts.write_offset(f)
+        }
+    }
+
+    impl fmt::Display for XsdDate<NaiveDate> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            Parts {
+                year: self.0.year(),
+                month: self.0.month() as u8,
+                day: self.0.day() as u8,
+                weekday: self.0.weekday().num_days_from_monday() as u8,
+                hour: 0,
+                minute: 0,
+                second: 0,
+                nanosecond: 0,
+                offset: 0,
+            }.write_date(f)
+        }
+    }
+}
+
+#[cfg(feature = "time")]
+mod time_impls {
+    use std::fmt;
+    use time::{Date, OffsetDateTime};
+    use super::{Parts, Rfc3339, Rfc822, XsdDate};
+
+    fn parts(dt: &OffsetDateTime) -> Parts {
+        Parts {
+            year: dt.year(),
+            month: dt.month() as u8,
+            day: dt.day(),
+            weekday: dt.weekday().number_days_from_monday(),
+            hour: dt.hour(),
+            minute: dt.minute(),
+            second: dt.second(),
+            nanosecond: dt.nanosecond(),
+            offset: dt.offset().whole_seconds(),
+        }
+    }
+
+    impl fmt::Display for Rfc822<OffsetDateTime> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            parts(&self.0).write_rfc822(f)
+        }
+    }
+
+    impl fmt::Display for Rfc3339<OffsetDateTime> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            parts(&self.0).write_rfc3339(f)
+        }
+    }
+
+    impl fmt::Display for XsdDate<OffsetDateTime> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            let parts = parts(&self.0);
+            parts.write_date(f)?;
+            parts.write_offset(f)
+        }
+    }
+
+    impl fmt::Display for XsdDate<Date> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            let dt = self.0.midnight().assume_utc();
+            parts(&dt).write_date(f)
+        }
+    }
+}
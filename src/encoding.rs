@@ -0,0 +1,160 @@
+use std::io;
+
+
+
+/// Character encoding of a [`Document`][crate::Document], specified via the
+/// `encoding` in the XML prolog.
+///
+/// Documents are always built as `String`, so [`Document::as_str`] and
+/// friends return UTF-8 regardless of this encoding. Use
+/// [`Document::to_bytes`] or [`Document::write_to`] to get the encoded
+/// document. Characters in text and attribute values that can't be
+/// represented in the encoding are written as character references.
+///
+/// [`Document::as_str`]: crate::Document::as_str
+/// [`Document::to_bytes`]: crate::Document::to_bytes
+/// [`Document::write_to`]: crate::Document::write_to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Encoding {
+    /// `encoding="UTF-8"`, the default.
+    #[default]
+    Utf8,
+
+    /// Little endian UTF-16, specified as `encoding="UTF-16"` or
+    /// `encoding="UTF-16LE"` in `xml!`. The prolog always says `UTF-16` and a
+    /// byte order mark is always written, as required by the XML spec.
+    Utf16Le,
+
+    /// Big endian UTF-16, specified as `encoding="UTF-16BE"` in `xml!`. Like
+    /// [`Encoding::Utf16Le`], the prolog says `UTF-16` and there is always a
+    /// byte order mark.
+    Utf16Be,
+
+    /// `encoding="ISO-8859-1"` (Latin-1), which can represent U+0000 to
+    /// U+00FF.
+    Latin1,
+
+    /// `encoding="windows-1252"`, which is Latin-1 with printable characters
+    /// like `€` instead of the C1 control characters.
+    Windows1252,
+}
+
+impl Encoding {
+    /// The name as written in the XML prolog.
+    pub fn name(self) -> &'static str {
+        match self {
+            Encoding::Utf8 => "UTF-8",
+            Encoding::Utf16Le | Encoding::Utf16Be => "UTF-16",
+            Encoding::Latin1 => "ISO-8859-1",
+            Encoding::Windows1252 => "windows-1252",
+        }
+    }
+
+    /// Returns whether `c` can be represented in this encoding.
+    pub(crate) fn can_encode(self, c: char) -> bool {
+        match self {
+            Encoding::Utf8 | Encoding::Utf16Le | Encoding::Utf16Be => true,
+            Encoding::Latin1 => (c as u32) <= 0xFF,
+            Encoding::Windows1252 => to_windows_1252(c).is_some(),
+        }
+    }
+
+    /// Writes `s` encoded to `w`, with a byte order mark if `bom` is set or
+    /// the encoding requires one. Characters that can't be encoded result in
+    /// an error.
+    pub(crate) fn write(self, s: &str, bom: bool, mut w: impl io::Write) -> io::Result<()> {
+        if self == Encoding::Utf8 {
+            if bom {
+                w.write_all(b"\xEF\xBB\xBF")?;
+            }
+            return w.write_all(s.as_bytes());
+        }
+
+        // Encode in chunks to avoid allocating a second copy of the document.
+        let mut chunk = [0u8; 1024];
+        let mut len = 0;
+        if matches!(self, Encoding::Utf16Le | Encoding::Utf16Be) {
+            len = self.encode('\u{FEFF}', &mut chunk);
+        }
+        for c in s.chars() {
+            if len + 4 > chunk.len() {
+                w.write_all(&chunk[..len])?;
+                len = 0;
+            }
+            if !self.can_encode(c) {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, format!(
+                    "character '{c}' in name, comment or processing instruction \
+                        can't be represented in {}",
+                    self.name(),
+                )));
+            }
+            len += self.encode(c, &mut chunk[len..]);
+        }
+        w.write_all(&chunk[..len])
+    }
+
+    /// Writes the encoded `c` to the start of `out`, returning the number of
+    /// bytes. `c` has to be encodable and `out` at least 4 bytes long.
+    fn encode(self, c: char, out: &mut [u8]) -> usize {
+        match self {
+            Encoding::Utf8 => c.encode_utf8(out).len(),
+            Encoding::Utf16Le | Encoding::Utf16Be => {
+                let mut units = [0u16; 2];
+                let units = c.encode_utf16(&mut units);
+                for (i, unit) in units.iter().enumerate() {
+                    let bytes = match self {
+                        Encoding::Utf16Le => unit.to_le_bytes(),
+                        _ => unit.to_be_bytes(),
+                    };
+                    out[2 * i..2 * i + 2].copy_from_slice(&bytes);
+                }
+                2 * units.len()
+            }
+            Encoding::Latin1 => {
+                out[0] = c as u8;
+                1
+            }
+            Encoding::Windows1252 => {
+                out[0] = to_windows_1252(c).unwrap();
+                1
+            }
+        }
+    }
+}
+
+/// Maps `c` to its windows-1252 byte, if there is one. The bytes 0x81, 0x8D,
+/// 0x8F, 0x90 and 0x9D are not assigned.
+fn to_windows_1252(c: char) -> Option<u8> {
+    let b = match c {
+        '\u{0}'..='\u{7F}' | '\u{A0}'..='\u{FF}' => return Some(c as u8),
+        '€' => 0x80,
+        '‚' => 0x82,
+        'ƒ' => 0x83,
+        '„' => 0x84,
+        '…' => 0x85,
+        '†' => 0x86,
+        '‡' => 0x87,
+        'ˆ' => 0x88,
+        '‰' => 0x89,
+        'Š' => 0x8A,
+        '‹' => 0x8B,
+        'Œ' => 0x8C,
+        'Ž' => 0x8E,
+        '‘' => 0x91,
+        '’' => 0x92,
+        '“' => 0x93,
+        '”' => 0x94,
+        '•' => 0x95,
+        '–' => 0x96,
+        '—' => 0x97,
+        '˜' => 0x98,
+        '™' => 0x99,
+        'š' => 0x9A,
+        '›' => 0x9B,
+        'œ' => 0x9C,
+        'ž' => 0x9E,
+        'Ÿ' => 0x9F,
+        _ => return None,
+    };
+    Some(b)
+}
@@ -0,0 +1,441 @@
+use std::borrow::Cow;
+
+
+
+/// Specifies how the XML should be formatted.
+///
+/// Start with one of the presets [`Format::terse`] or [`Format::pretty`] and
+/// adjust it with the builder methods. Pass to [`xml`][crate::xml] like this:
+///
+/// ```
+/// use ogrim::{xml, Format, Quote};
+///
+/// let doc = xml!(
+///     #[format = Format::pretty().indentation("\t").quote(Quote::Single)]
+///     <?xml version="1.0" ?>
+///     <foo bar="baz"><child /></>
+/// );
+///
+/// assert_eq!(doc.as_str(), concat!(
+///     "<?xml version='1.0' encoding='UTF-8'?>\n",
+///     "<foo bar='baz'>\n",
+///     "\t<child />\n",
+///     "</foo>\n",
+/// ));
+/// ```
+///
+/// After `format = ` you can pass any Rust expression, also referencing
+/// variables, for example to make formatting conditional. If not specified,
+/// [`Format::terse`] is used.
+///
+///
+/// # Pretty printing
+///
+/// If an indentation is set, every element is put on its own line and
+/// indented, unless it is inside an element containing text. Whitespace
+/// there would change the text content, so elements with text content are
+/// kept on one line. This includes mixed content like `<p><b>"a"</b>"b"</p>`,
+/// as [`xml!`][crate::xml] knows which elements contain text before writing
+/// them. Only text written by closures (e.g. `{|doc| ...}`) is not known in
+/// advance: as the document is written in one go, whitespace before the
+/// first such text of an element can't be removed afterwards.
+///
+/// No whitespace is added inside elements with `xml:space="preserve"`
+/// either, nor inside elements marked with `#[preserve_space]` in
+/// [`xml!`][crate::xml]. Use that for whitespace-sensitive content like code
+/// samples.
+///
+/// ```
+/// use ogrim::{xml, Format};
+///
+/// let doc = xml!(
+///     #[format = Format::pretty()]
+///     <?xml version="1.0" ?>
+///     <foo>
+///         <title>"Foxes"</>
+///         <p>"They are " <b>"cute"</b>"!"</p>
+///         <p><b>"Foxes"</b>" are cute"</p>
+///         <svg xml:space="preserve"><g><text>"Fox"</></></>
+///         #[preserve_space]
+///         <code><line /><line /></code>
+///     </foo>
+/// );
+///
+/// assert_eq!(doc.as_str(), concat!(
+///     "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+///     "<foo>\n",
+///     "  <title>Foxes</title>\n",
+///     "  <p>They are <b>cute</b>!</p>\n",
+///     "  <p><b>Foxes</b> are cute</p>\n",
+///     "  <svg xml:space=\"preserve\"><g><text>Fox</text></g></svg>\n",
+///     "  <code><line /><line /></code>\n",
+///     "</foo>\n",
+/// ));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Format {
+    pub(crate) indentation: Option<Cow<'static, str>>,
+    pub(crate) newline: Newline,
+    pub(crate) trailing_newline: bool,
+    pub(crate) space_before_slash: bool,
+    pub(crate) quote: Quote,
+    pub(crate) max_width: Option<usize>,
+    pub(crate) attr_order: AttrOrder,
+    pub(crate) empty_elements: EmptyElements,
+    pub(crate) ascii_only: bool,
+    pub(crate) carriage_returns: CarriageReturns,
+    pub(crate) bool_attrs: BoolAttrs,
+}
+
+impl Format {
+    /// Minimized, as short as possible: no newlines or indentation, no
+    /// trailing newline and no space before `/>`.
+    pub fn terse() -> Self {
+        Self {
+            indentation: None,
+            newline: Newline::Lf,
+            trailing_newline: false,
+            space_before_slash: false,
+            quote: Quote::Double,
+            max_width: None,
+            attr_order: AttrOrder::Source,
+            empty_elements: EmptyElements::AsWritten,
+            ascii_only: false,
+            carriage_returns: CarriageReturns::Keep,
+            bool_attrs: BoolAttrs::Name,
+        }
+    }
+
+    /// Pretty printed for human consumption: indented with two spaces, with
+    /// trailing newline and a space before `/>`. See the
+    /// [type docs](#pretty-printing) for details.
+    pub fn pretty() -> Self {
+        Self {
+            indentation: Some(Cow::Borrowed("  ")),
+            trailing_newline: true,
+            space_before_slash: true,
+            ..Self::terse()
+        }
+    }
+
+    /// Sets the string with which to indent, e.g. `"\t"`. This enables
+    /// pretty printing, so it also works on [`Format::terse`].
+    pub fn indentation(mut self, indentation: impl Into<Cow<'static, str>>) -> Self {
+        self.indentation = Some(indentation.into());
+        self
+    }
+
+    /// Sets which line ending to use for newlines added by pretty printing
+    /// and for the trailing newline. Default: [`Newline::Lf`].
+    pub fn newline(mut self, newline: Newline) -> Self {
+        self.newline = newline;
+        self
+    }
+
+    /// Sets whether to end the document with a newline.
+    pub fn trailing_newline(mut self, trailing_newline: bool) -> Self {
+        self.trailing_newline = trailing_newline;
+        self
+    }
+
+    /// Sets whether to write empty elements as `<foo />` instead of `<foo/>`.
+    pub fn space_before_slash(mut self, space_before_slash: bool) -> Self {
+        self.space_before_slash = space_before_slash;
+        self
+    }
+
+    /// Sets the quote character for attribute values and the XML
+    /// declaration. Default: [`Quote::Double`].
+    pub fn quote(mut self, quote: Quote) -> Self {
+        self.quote = quote;
+        self
+    }
+
+    /// Sets the maximum line width in characters when pretty printing. If a
+    /// start tag exceeds it, all attributes but the first are put on their
+    /// own line, aligned with the first one. Default: no limit.
+    ///
+    /// ```
+    /// use ogrim::{xml, Format};
+    ///
+    /// let doc = xml!(
+    ///     #[format = Format::pretty().max_width(40)]
+    ///     <?xml version="1.0" ?>
+    ///     <feed>
+    ///         <link rel="alternate" type="text/html" href="https://example.com" />
+    ///     </feed>
+    /// );
+    ///
+    /// assert_eq!(doc.as_str(), concat!(
+    ///     "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+    ///     "<feed>\n",
+    ///     "  <link rel=\"alternate\"\n",
+    ///     "        type=\"text/html\"\n",
+    ///     "        href=\"https://example.com\" />\n",
+    ///     "</feed>\n",
+    /// ));
+    /// ```
+    pub fn max_width(mut self, max_width: usize) -> Self {
+        self.max_width = Some(max_width);
+        self
+    }
+
+    /// Sets the order in which attributes are written. Default:
+    /// [`AttrOrder::Source`].
+    ///
+    /// ```
+    /// use ogrim::{xml, AttrOrder, Format};
+    ///
+    /// let extra = [("b", 2), ("a", 1)];
+    /// let doc = xml!(
+    ///     #[format = Format::terse().attr_order(AttrOrder::Sorted)]
+    ///     <?xml version="1.0" ?>
+    ///     <foo z="26" {..extra} c="3" />
+    /// );
+    ///
+    /// assert_eq!(doc.as_str(), concat!(
+    ///     r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+    ///     r#"<foo a="1" b="2" c="3" z="26"/>"#,
+    /// ));
+    /// ```
+    pub fn attr_order(mut self, attr_order: AttrOrder) -> Self {
+        self.attr_order = attr_order;
+        self
+    }
+
+    /// Sets how elements without content are written. Default:
+    /// [`EmptyElements::AsWritten`].
+    ///
+    /// ```
+    /// use ogrim::{xml, EmptyElements, Format};
+    ///
+    /// let items: &[&str] = &[];
+    /// let doc = xml!(
+    ///     #[format = Format::terse().empty_elements(EmptyElements::Collapse)]
+    ///     <?xml version="1.0" ?>
+    ///     <foo>
+    ///         <items>{|doc| for item in items { ogrim::xml!(doc, <item>{item}</>); }}</>
+    ///         <br></br>
+    ///     </foo>
+    /// );
+    ///
+    /// assert_eq!(doc.as_str(), concat!(
+    ///     r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+    ///     r#"<foo><items/><br/></foo>"#,
+    /// ));
+    /// ```
+    pub fn empty_elements(mut self, empty_elements: EmptyElements) -> Self {
+        self.empty_elements = empty_elements;
+        self
+    }
+
+    /// Sets whether to write all non-ASCII characters in text and attribute
+    /// values as character references, e.g. `&#xE4;` for `ä`. This is
+    /// useful for consumers that can't deal with raw UTF-8. Names, comments
+    /// and processing instructions can't contain references, so non-ASCII
+    /// characters in those are still written as is. Default: `false`.
+    ///
+    /// ```
+    /// use ogrim::{xml, Format};
+    ///
+    /// let doc = xml!(
+    ///     #[format = Format::terse().ascii_only(true)]
+    ///     <?xml version="1.0" ?>
+    ///     <foo city="Zürich">"🦊 & co" <bar>"\u{0}"</bar></foo>
+    /// );
+    ///
+    /// // The replacement for the forbidden U+0000 is written as reference, too.
+    /// assert_eq!(doc.as_str(), concat!(
+    ///     r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+    ///     r#"<foo city="Z&#xFC;rich">&#x1F98A; &amp; co<bar>&#xFFFD;</bar></foo>"#,
+    /// ));
+    /// assert!(doc.as_str().is_ascii());
+    /// ```
+    pub fn ascii_only(mut self, ascii_only: bool) -> Self {
+        self.ascii_only = ascii_only;
+        self
+    }
+
+    /// Sets what to do with carriage returns (`\r`) in text. In attribute
+    /// values, they are always written as `&#xD;`. Default:
+    /// [`CarriageReturns::Keep`].
+    ///
+    /// ```
+    /// use ogrim::{xml, CarriageReturns, Format};
+    ///
+    /// let text = "line 1\r\nline 2";
+    /// let escape = Format::terse().carriage_returns(CarriageReturns::Escape);
+    /// let normalize = Format::terse().carriage_returns(CarriageReturns::Normalize);
+    /// let a = xml!(#[format = escape] <?xml version="1.0" ?> <a>{text}</a>);
+    /// let b = xml!(#[format = normalize] <?xml version="1.0" ?> <a>{text}</a>);
+    ///
+    /// assert!(a.as_str().ends_with("<a>line 1&#xD;\nline 2</a>"));
+    /// assert!(b.as_str().ends_with("<a>line 1\nline 2</a>"));
+    /// ```
+    pub fn carriage_returns(mut self, carriage_returns: CarriageReturns) -> Self {
+        self.carriage_returns = carriage_returns;
+        self
+    }
+
+    /// Sets the value of boolean attributes written via `name?={true}` in
+    /// [`xml!`][crate::xml]. Default: [`BoolAttrs::Name`].
+    ///
+    /// This does not apply to trees: [`xml_tree!`][crate::xml_tree] stores
+    /// boolean attributes as regular attributes with their name as value
+    /// (e.g. `hidden="hidden"`), which are written as is with any format.
+    ///
+    /// ```
+    /// use ogrim::{xml, BoolAttrs, Format};
+    ///
+    /// let doc = xml!(
+    ///     #[format = Format::terse().bool_attrs(BoolAttrs::True)]
+    ///     <?xml version="1.0" ?>
+    ///     <option selected?={true} />
+    /// );
+    ///
+    /// assert!(doc.as_str().ends_with(r#"<option selected="true"/>"#));
+    /// ```
+    pub fn bool_attrs(mut self, bool_attrs: BoolAttrs) -> Self {
+        self.bool_attrs = bool_attrs;
+        self
+    }
+}
+
+impl Default for Format {
+    fn default() -> Self {
+        Self::terse()
+    }
+}
+
+/// Line ending used by [`Format`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Newline {
+    /// `\n`
+    #[default]
+    Lf,
+
+    /// `\r\n`
+    CrLf,
+}
+
+impl Newline {
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            Newline::Lf => "\n",
+            Newline::CrLf => "\r\n",
+        }
+    }
+}
+
+/// Quote character for attribute values, used by [`Format`]. The chosen
+/// character is escaped in values, the other one is not.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Quote {
+    /// `"`, escaped as `&quot;`.
+    #[default]
+    Double,
+
+    /// `'`, escaped as `&apos;`.
+    Single,
+
+    /// Per attribute value, `'` if the value contains more `"` than `'`,
+    /// otherwise `"`. The XML declaration uses `"`.
+    ///
+    /// ```
+    /// use ogrim::{xml, Format, Quote};
+    ///
+    /// let json = r#"{"id": 3}"#;
+    /// let doc = xml!(
+    ///     #[format = Format::terse().quote(Quote::Auto)]
+    ///     <?xml version="1.0" ?>
+    ///     <div data-json={json} title="it's" />
+    /// );
+    ///
+    /// assert_eq!(doc.as_str(), concat!(
+    ///     r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+    ///     r#"<div data-json='{"id": 3}' title="it's"/>"#,
+    /// ));
+    /// ```
+    Auto,
+}
+
+impl Quote {
+    /// The quote character for the XML declaration.
+    pub(crate) fn char(self) -> char {
+        match self {
+            Quote::Double | Quote::Auto => '"',
+            Quote::Single => '\'',
+        }
+    }
+
+    /// The quote character for the given (escaped, but with unescaped
+    /// quotes) attribute value.
+    pub(crate) fn char_for(self, value: &str) -> char {
+        match self {
+            Quote::Auto if value.matches('"').count() > value.matches('\'').count() => '\'',
+            _ => self.char(),
+        }
+    }
+}
+
+/// Order in which attributes of an element are written, used by [`Format`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AttrOrder {
+    /// In the order they are specified, including `{..iter}` fills.
+    #[default]
+    Source,
+
+    /// Sorted by name (by comparing the UTF-8 bytes). This is useful for
+    /// reproducible output, e.g. if attributes come from a `HashMap`.
+    Sorted,
+}
+
+/// How elements without content are written, used by [`Format`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EmptyElements {
+    /// `<foo />` is written as empty-element tag, `<foo></>` as start and end
+    /// tag, even if no content was written.
+    #[default]
+    AsWritten,
+
+    /// Always write start and end tag, i.e. `<foo></foo>`. Some consumers
+    /// like old HTML parsers require this.
+    Expand,
+
+    /// Write an empty-element tag, i.e. `<foo/>`, whenever an element ends
+    /// up without content, e.g. because all children were skipped at
+    /// runtime.
+    Collapse,
+}
+
+/// What to do with carriage returns (`\r`) in text, used by [`Format`].
+///
+/// XML parsers normalize `\r\n` and single `\r` to `\n`, so text containing
+/// `\r` does not round-trip unless it is escaped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CarriageReturns {
+    /// Write them as is, leaving normalization to the parser.
+    #[default]
+    Keep,
+
+    /// Write them as `&#xD;` so that they are preserved, as e.g. required by
+    /// canonical XML.
+    Escape,
+
+    /// Replace `\r\n` and single `\r` with `\n`, like parsers would.
+    Normalize,
+}
+
+/// Value of boolean attributes like `hidden?={true}`, used by [`Format`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BoolAttrs {
+    /// The attribute name, e.g. `hidden="hidden"`, as in XHTML.
+    #[default]
+    Name,
+
+    /// `hidden="true"`, like `xsd:boolean`.
+    True,
+
+    /// `hidden=""`, which HTML also treats as set.
+    Empty,
+}
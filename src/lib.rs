@@ -1,13 +1,16 @@
 //! XML builder macro letting you write XML inside Rust code (similar to `serde_json::json!`).
 //!
-//! This library only builds a string, not some kind of tree representing the
+//! [`xml!`] only builds a string, not some kind of tree representing the
 //! XML document. Thus, you cannot introspect it after building. So this is
-//! just a better `format!` for building XML.
+//! just a better `format!` for building XML. If you do need to inspect or
+//! modify the document before writing it, use [`xml_tree!`] instead, which
+//! builds an owned [`tree::Element`].
 //!
-//! There are no memory allocations in this library except by the `String` that
-//! is being built. Not even temporarily, not even for escaping values. This
-//! should make it quite speedy and at least as fast as hand written string
-//! building.
+//! There are very few memory allocations in `xml!`: besides the `String`
+//! that is being built, only a buffer for the current start tag, which is
+//! reused for all elements. Values are escaped without temporary
+//! allocations. This should make it quite speedy and at least as fast as
+//! hand written string building.
 //!
 //!
 //! # Mini example
@@ -48,9 +51,18 @@
 //! ```
 //!
 //!
+//! # Crate features
+//!
+//! - `chrono` and `time`: formatting of the date and time types of these
+//!   crates via the wrappers in the `datetime` module, e.g. as RFC 822 for
+//!   RSS. Both are disabled by default.
+//!
+//!
 //! # Limitations and notes
 //!
-//! - This crate only lets you build `UTF-8` encoded XML documents.
+//! - Documents are always built as UTF-8 `String`. Other encodings like
+//!   UTF-16 or ISO-8859-1 are supported by encoding the finished document,
+//!   see [`Encoding`].
 //! - Text content of nodes has to be quoted (e.g. `<foo>"hello"</foo>` instead
 //!   of `<foo>hello</foo>`).
 //! - Writing *names* (i.e. tag and attribute names) has some special cases. In
@@ -59,40 +71,77 @@
 //!   just use a string literal: `<"weird3.14exml-name:" />`.
 //!
 //!   <details>
-//!     <summary>The gory details</summary>
+//!   <summary>The gory details</summary>
 //!
-//!     First, talking about characters beyond ASCII, XML names allow some chars
-//!     that Rust identifiers do not allow. Those are just not part of the Rust
-//!     lexicographical grammar and hence, using a string literal is necessary
-//!     in that case. But you likely won't run into this. For completeness,
-//!     [here are all characters][1] you could legally write in XML names, but
-//!     not in Rust identifiers.
+//!   First, talking about characters beyond ASCII, XML names allow some chars
+//!   that Rust identifiers do not allow. Those are just not part of the Rust
+//!   lexicographical grammar and hence, using a string literal is necessary
+//!   in that case. But you likely won't run into this. For completeness,
+//!   [here are all characters][1] you could legally write in XML names, but
+//!   not in Rust identifiers.
 //!
-//!     Further, `- : .` are all not part of Rust identifier, but instead
-//!     treated by Rust as "puncuation". And Rust macros have no information
-//!     about whitespace at all, so these three inputs are the same:
-//!     - `<foo:bar: baz="3">`
-//!     - `<foo:bar :baz="3">`
-//!     - `<foo: bar:baz="3">`
+//!   Further, `- : .` are all not part of Rust identifier, but instead
+//!   treated by Rust as "puncuation". And Rust macros have no information
+//!   about whitespace at all, so these three inputs are the same:
+//!   - `<foo:bar: baz="3">`
+//!   - `<foo:bar :baz="3">`
+//!   - `<foo: bar:baz="3">`
 //!
-//!     This library uses some best effort guesses to disambiguate this. If you
-//!     don't use `- : .` at the end of an XML name it should work fine.
-//!     Finally, due to these characters being treated as punctuation, digits
-//!     after these puncuations are parsed as numeric literals, which brings a
-//!     whole new bag of weird behavior. For example, `foo:27eels` fails to
-//!     parse as `27e` is parsed as a floating point literal with exponent...
-//!     but the actual exponent is missing.
+//!   This library uses some best effort guesses to disambiguate this. If you
+//!   don't use `- : .` at the end of an XML name it should work fine.
+//!   Finally, due to these characters being treated as punctuation, digits
+//!   after these puncuations are parsed as numeric literals, which brings a
+//!   whole new bag of weird behavior. For example, `foo:27eels` fails to
+//!   parse as `27e` is parsed as a floating point literal with exponent...
+//!   but the actual exponent is missing.
 //!
-//!     Again: for most normal names, everything should just work. For
-//!     everything else, know these rules or just use a string literal
-//!     instead.
+//!   Again: for most normal names, everything should just work. For
+//!   everything else, know these rules or just use a string literal
+//!   instead.
 //!
 //!   </details>
 //!
 //! [1]: https://util.unicode.org/UnicodeJsps/list-unicodeset.jsp?a=%5B%5BA-Z_%3A%5C-.a-z0-9%5Cu00B7%5Cu00C0-%5Cu00D6%5Cu00D8-%5Cu00F6%5Cu00F8-%5Cu036F%5Cu0370-%5Cu037D%5Cu037F-%5Cu1FFF%5Cu200C-%5Cu200D%5Cu203F-%5Cu2040%5Cu2070-%5Cu218F%5Cu2C00-%5Cu2FEF%5Cu3001-%5CuD7FF%5CuF900-%5CuFDCF%5CuFDF0-%5CuFFFD%5CU00010000-%5CU000EFFFF%5D-%5B%3AXID_Continue%3A%5D%5D&esc=on&g=&i=
 
 use core::fmt;
-use std::{fmt::Write, matches, unreachable};
+use std::{fmt::Write, io, matches, ops::Range};
+
+
+mod assert;
+mod attr;
+mod c14n;
+mod check;
+#[cfg(any(feature = "chrono", feature = "time"))]
+pub mod datetime;
+mod encoding;
+mod format;
+mod pattern;
+mod read;
+pub mod tree;
+
+pub use self::{
+    attr::{join, AttrValue, Join},
+    c14n::C14n,
+    check::check,
+    encoding::Encoding,
+    format::{AttrOrder, BoolAttrs, CarriageReturns, EmptyElements, Format, Newline, Quote},
+    read::{Escaped, Event, ParseError, Reader},
+};
+
+#[doc(hidden)]
+pub use self::{
+    assert::assert_xml_eq as __assert_xml_eq,
+    attr::{
+        OptionalAttr as __OptionalAttr,
+        OptionalValue as __OptionalValue,
+        ViaAttrValue as __ViaAttrValue,
+        ViaDisplay as __ViaDisplay,
+        ViaOptAttrValue as __ViaOptAttrValue,
+        ViaOptionalAttr as __ViaOptionalAttr,
+        Wrap as __AttrWrap,
+    },
+    pattern::Matcher as __Matcher,
+};
 
 
 
@@ -127,7 +176,23 @@ use std::{fmt::Write, matches, unreachable};
 /// You can interpolate variables and other Rust expressions in the XML literal,
 /// as shown in the previous example. Writing `{...}` in place of an attribute
 /// value or a element's content treats the inner part as Rust expression,
-/// which must evaluate to something that implements [`fmt::Display`].
+/// which must evaluate to something that implements [`fmt::Display`]. For
+/// attribute values, [`AttrValue`] is used instead if implemented, which
+/// writes e.g. infinite floats as `INF` and omits attributes set to `None`.
+///
+/// All values are escaped as necessary. In attribute values, this includes
+/// tabs and line breaks, which XML parsers would otherwise normalize to
+/// spaces:
+///
+/// ```rust
+/// use ogrim::xml;
+///
+/// let doc = xml!(<?xml version="1.0" ?><foo desc={"a <b>\n\tc"}>"a <b>\n\tc"</foo>);
+/// assert_eq!(doc.as_str(), concat!(
+///     r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+///     "<foo desc=\"a &lt;b&gt;&#xA;&#x9;c\">a &lt;b&gt;\n\tc</foo>",
+/// ));
+/// ```
 ///
 /// There is one special form of interpolations that look like a closure:
 /// `{|doc| ...}`. As if this were a closure, your code can access `doc` which
@@ -183,6 +248,115 @@ use std::{fmt::Write, matches, unreachable};
 /// has to be performed at runtime. If passed invalid XML names, this will
 /// panic.
 ///
+/// ## Optional attribute syntax `name?={value}`
+///
+/// For the common case of a single optional attribute, you can write
+/// `name?={value}` instead, where `value` is an `Option<T>` with `T:
+/// fmt::Display` or a `bool`. Like with `name={value}`, `Some` values are
+/// written via [`AttrValue`] if `T` implements it. The attribute is omitted if
+/// the value is `None` or `false`. For `true`, a boolean attribute like
+/// `hidden="hidden"` is
+/// written; the value can be changed via [`Format::bool_attrs`]. Unlike with
+/// `{..iter}`, the name is checked at compile time.
+///
+/// ```rust
+/// use ogrim::xml;
+///
+/// let description = Some("Lorem Ipsum");
+/// let title: Option<String> = None;
+/// let max = Some(f64::INFINITY);
+/// let doc = xml!(
+///     <?xml version="1.0" ?>
+///     <input description?={description} title?={title} max?={max}
+///         hidden?={true} checked?={false} />
+/// );
+///
+/// assert_eq!(doc.as_str(), concat!(
+///     r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+///     r#"<input description="Lorem Ipsum" max="INF" hidden="hidden"/>"#,
+/// ));
+/// ```
+///
+/// ## Attribute shorthand `{name}`
+///
+/// Like struct field init shorthand in Rust, `{id}` is short for `id={id}`.
+/// This only works for a single identifier, and a leading `r#` of raw
+/// identifiers is stripped from the attribute name.
+///
+/// ```rust
+/// use ogrim::xml;
+///
+/// let id = 7;
+/// let r#type = "book";
+/// let doc = xml!(<?xml version="1.0" ?><item {id} {r#type} />);
+///
+/// assert_eq!(doc.as_str(), concat!(
+///     r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+///     r#"<item id="7" type="book"/>"#,
+/// ));
+/// ```
+///
+/// ## Conditional attributes with `if`
+///
+/// Groups of attributes can be included conditionally with `if cond { ...
+/// }`, optionally followed by `else { ... }` or `else if`. Inside the
+/// braces, all attribute forms described above can be used.
+///
+/// ```rust
+/// use ogrim::xml;
+///
+/// let is_external = true;
+/// let doc = xml!(
+///     <?xml version="1.0" ?>
+///     <a href="https://example.com" if is_external {
+///         target="_blank" rel="noopener"
+///     } else {
+///         class="internal"
+///     }>"Example"</a>
+/// );
+///
+/// assert_eq!(doc.as_str(), concat!(
+///     r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+///     r#"<a href="https://example.com" target="_blank" rel="noopener">Example</a>"#,
+/// ));
+/// ```
+///
+/// `if` and `else` are only keywords if they are not the start of an
+/// attribute name, i.e. not followed by `=`, `?=`, `-`, `.` or `:`:
+///
+/// ```rust
+/// use ogrim::xml;
+///
+/// let doc = xml!(
+///     <?xml version="1.0" ?>
+///     <a xmlns:else="urn:e" if-none-match="x" if true { b="1" } else:foo="y" />
+/// );
+///
+/// assert_eq!(doc.as_str(), concat!(
+///     r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+///     r#"<a xmlns:else="urn:e" if-none-match="x" b="1" else:foo="y"/>"#,
+/// ));
+/// ```
+///
+/// ## List values `name={..iter}`
+///
+/// For whitespace-separated lists like `class` or `rel`, `name={..iter}`
+/// writes all items of `iter` separated by spaces, see [`join`]. The iterator
+/// must be `Clone`; pass collections by reference (e.g. `{..&vec}`) to avoid
+/// copying them.
+///
+/// ```rust
+/// use ogrim::xml;
+///
+/// let rel = ["noopener", "noreferrer"];
+/// let doc = xml!(<?xml version="1.0" ?><a rel={..rel} />);
+///
+/// assert_eq!(doc.as_str(), concat!(
+///     r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+///     r#"<a rel="noopener noreferrer"/>"#,
+/// ));
+/// ```
+///
 ///
 /// # Create new document (entry point)
 ///
@@ -194,7 +368,7 @@ use std::{fmt::Write, matches, unreachable};
 ///
 /// let doc = xml!(
 ///     // Optional: specify meta/formatting attributes
-///     #[format = Format::Pretty { indentation: "  " }]
+///     #[format = Format::pretty()]
 ///     <?xml version="1.0" encoding="UTF-8" ?>   // XML prolog
 ///     <foo bar="baz">    // root element
 ///         // ...
@@ -204,10 +378,54 @@ use std::{fmt::Write, matches, unreachable};
 /// println!("{}", doc.as_str()); // Print XML
 /// ```
 ///
-/// Currently the only supported meta attribute is `format`. See [`Format`].
+/// The supported meta attributes are:
+/// - `format`: how the XML is formatted, see [`Format`].
+/// - `char_policy`: what to do with characters that are not allowed in XML
+///   documents, see [`CharPolicy`].
+/// - `bom`: a `bool` expression specifying whether [`Document::to_bytes`] and
+///   [`Document::write_to`] start with a byte order mark. Only relevant for
+///   UTF-8, as UTF-16 always has one and the other encodings never do.
+///
+/// Additionally, any element can be prefixed with `#[preserve_space]` to
+/// disable pretty printing inside it, see [`Format`].
+///
+/// The XML prolog is required. Specifying `encoding` is optional and
+/// defaults to `"UTF-8"`. See [`Encoding`] for the other supported encodings.
+/// Characters that can't be represented in the encoding are written as
+/// character references.
+///
+/// ```rust
+/// use ogrim::xml;
+///
+/// let doc = xml!(
+///     <?xml version="1.0" encoding="ISO-8859-1" ?>
+///     <foo>"Grüße, €5"<bar>"\u{0}"</bar></foo>
+/// );
+///
+/// assert_eq!(doc.to_bytes(), b"<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?>\
+///     <foo>Gr\xFC\xDFe, &#x20AC;5<bar>&#xFFFD;</bar></foo>");
+/// ```
+///
+/// The `version` in the prolog also influences how text and attribute values
+/// are written. In XML 1.1 documents, the control characters U+0001 to U+001F
+/// (except tab, CR and LF) and U+007F to U+009F are written as character
+/// references, as XML 1.1 only allows them in that form. The same is done for
+/// NEL (U+0085) and LSEP (U+2028) which XML 1.1 parsers would otherwise
+/// normalize to `\n`.
+///
+/// ```rust
+/// use ogrim::xml;
 ///
-/// The XML prolog is required. Specifying `encoding` is optional and if
-/// specified, must be `"UTF-8"`.
+/// let doc = xml!(
+///     <?xml version="1.1" ?>
+///     <foo>{"esc: \u{1B}, nel: \u{85}"}</>
+/// );
+///
+/// assert_eq!(doc.as_str(), concat!(
+///     r#"<?xml version="1.1" encoding="UTF-8"?>"#,
+///     "<foo>esc: &#x1B;, nel: &#x85;</foo>",
+/// ));
+/// ```
 ///
 ///
 /// # Append to existing document & split up logic
@@ -272,19 +490,169 @@ use std::{fmt::Write, matches, unreachable};
 ///
 pub use ogrim_macros::xml;
 
+/// Extracts values from an XML document by matching it against a pattern
+/// written in `xml!` syntax.
+///
+/// The first argument is the input (anything implementing `AsRef<str>`),
+/// followed by the pattern for the root element. Optionally, `=> expr`
+/// follows, where `expr` can use all values bound in the pattern. The macro
+/// evaluates to `Result<T, ParseError>` where `T` is the type of `expr` (or
+/// `()` if omitted). The error contains the position and a description of
+/// the first part of the input not fitting the pattern (or not being
+/// well-formed).
+///
+/// ```rust
+/// use ogrim::xml_match;
+///
+/// let input = r#"
+///     <item id="27" lang="en">
+///         <title>Foxes &amp; friends</title>
+///         <author>Peter</author>
+///     </item>
+/// "#;
+///
+/// let (id, title) = xml_match!(input,
+///     <item id={id: u32}>
+///         <title>{title}</>
+///     </item>
+///     => (id, title)
+/// )?;
+///
+/// assert_eq!(id, 27);
+/// assert_eq!(title, "Foxes & friends");
+///
+/// let err = xml_match!(input, <item lang="de"></>).unwrap_err();
+/// assert_eq!(
+///     err.to_string(),
+///     "2:5: expected attribute 'lang' on 'item' to be 'de', but it is 'en'",
+/// );
+/// # Ok::<_, ogrim::ParseError>(())
+/// ```
+///
+/// The input can be any expression, e.g. a reference to a `String` or a
+/// [`Document`] built with `xml!`:
+///
+/// ```rust
+/// use ogrim::{xml, xml_match};
+///
+/// let s = String::from("<a><b>hi</b></a>");
+/// assert_eq!(xml_match!(&s, <a><b>{b}</></> => b)?, "hi");
+/// assert_eq!(xml_match!(s.as_str(), <a><b>{b}</></> => b)?, "hi");
+///
+/// let doc = xml!(<?xml version="1.0" ?><count n="3" />);
+/// assert_eq!(xml_match!(doc, <count n={n: u8} /> => n)?, 3);
+/// assert_eq!(xml_match!(doc.as_str(), <count n={n: u8} /> => n)?, 3);
+/// # Ok::<_, ogrim::ParseError>(())
+/// ```
+///
+/// The pattern is matched like this:
+/// - The root element and all elements in the pattern must exist in the input
+///   with the same name. Child elements are searched in order, skipping
+///   elements of other names. So the input can contain more elements than the
+///   pattern, which are ignored.
+/// - Attributes in the pattern must exist in the input. With a string literal
+///   (`lang="en"`), the value has to be equal. Attributes not mentioned in the
+///   pattern are ignored.
+/// - Text in the pattern must be the only child of its element. With a string
+///   literal (`<title>"Foo"</>`), the text content of the element has to be
+///   equal.
+/// - `{name}` in place of an attribute value or text binds the (unescaped)
+///   value to a `String` called `name`. With `{name: Type}`, the value is
+///   parsed via `FromStr` instead.
+/// - Comments, processing instructions and whitespace between elements are
+///   ignored.
+///
+/// The `{..iter}` and `{|doc| ...}` forms of `xml!` are not supported.
+pub use ogrim_macros::xml_match;
+
+/// Like [`xml!`], but builds a [`tree::Element`] instead of a string.
+///
+/// The resulting tree can be inspected and modified, and then written with
+/// any [`Format`]. See [the `tree` module][tree] for more information.
+///
+/// The syntax is the same as with `xml!`, except that no XML prolog and no
+/// meta attributes are allowed: the input is just the root element. Inside
+/// `{|doc| ...}`, `doc` is a [`&mut tree::Builder`][tree::Builder].
+/// `#[preserve_space]` on an element is kept in the tree, see
+/// [`tree::Element::preserve_space`].
+///
+/// ```rust
+/// use ogrim::{xml_tree, Format};
+///
+/// let tree = xml_tree!(<foo bar="baz">"hello"</foo>);
+/// assert_eq!(tree.attr("bar"), Some("baz"));
+///
+/// let tree = xml_tree!(<a>#[preserve_space] <code><l /><l /></code></a>);
+/// assert!(tree.to_document(Format::pretty()).as_str().ends_with(concat!(
+///     "<a>\n",
+///     "  <code><l /><l /></code>\n",
+///     "</a>\n",
+/// )));
+/// ```
+pub use ogrim_macros::xml_tree;
+
 
 
 
 /// A document, potentially still under construction.
 ///
-/// This is basically just a `String` inside. The only way to create a value of
-/// this type is by using [`xml!`]. That macro can also append to an existing
-/// `Document`. The only thing you can do on a document is get the string out
-/// of it.
+/// This is basically just a `String` inside. A value of this type is created
+/// by [`xml!`] or from an element tree via [`tree::Element::to_document`].
+/// `xml!` can also append to an existing `Document`. Besides getting the
+/// string out of it, the only thing you can do on a document is to write
+/// [`Event`]s to it.
 pub struct Document {
     buf: String,
     depth: u32,
     format: Format,
+    version: Version,
+    encoding: Encoding,
+    bom: bool,
+    char_policy: CharPolicy,
+    /// Whether we are between `open_tag` and `close_start_tag`.
+    in_start_tag: bool,
+    /// In pretty mode, newlines and indentation are only written once we
+    /// know that no text follows. This is set when one is due.
+    pending_newline: bool,
+    /// Depth of the outermost element containing text or having
+    /// `xml:space="preserve"`. Inside of it, no whitespace is added at all,
+    /// as it would change the text content.
+    mixed_depth: Option<u32>,
+    /// Buffer length right after the last start tag was closed, used to
+    /// detect elements without content.
+    content_start: usize,
+    /// Whether the root element has been closed.
+    after_root: bool,
+    /// The start tag currently being written, reused for all start tags.
+    tag: StartTag,
+    /// Names of the elements opened via `write_event` and not closed yet,
+    /// each preceded by a space, to check that `End` events match.
+    event_elems: String,
+}
+
+/// A start tag that is only written to the document once it is closed, as
+/// attribute order, line wrapping and quote characters can depend on all of
+/// its attributes.
+#[derive(Default)]
+struct StartTag {
+    name: String,
+    /// Names and escaped values of all attributes, back to back. Quotes in
+    /// values are escaped only when writing the tag, once the quote character
+    /// is known.
+    text: String,
+    attrs: Vec<TagAttr>,
+}
+
+/// An attribute of a `StartTag`, as ranges into `StartTag::text`.
+struct TagAttr {
+    name: Range<usize>,
+    value: Range<usize>,
+}
+
+impl AsRef<str> for Document {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
 }
 
 /// Just a wrapper around `write!().unwrap()` as writing to a string cannot fail.
@@ -303,11 +671,103 @@ impl Document {
         self.buf
     }
 
+    /// Returns the encoding specified in the XML prolog.
+    pub fn encoding(&self) -> Encoding {
+        self.encoding
+    }
+
+    /// Returns the document encoded with its [`Encoding`], prefixed with a
+    /// byte order mark if required or requested via `#[bom = true]`.
+    ///
+    /// Panics if a name, comment or processing instruction contains a
+    /// character that can't be represented in the encoding. Characters in
+    /// text and attribute values are written as character references
+    /// instead.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(self.buf.len() + 3);
+        if let Err(e) = self.write_to(&mut out) {
+            panic!("{e}");
+        }
+        out
+    }
+
+    /// Like [`Document::to_bytes`], but writes into the given writer,
+    /// encoding the document on the fly. If a character can't be
+    /// represented, an error of kind [`io::ErrorKind::InvalidData`] is
+    /// returned.
+    pub fn write_to(&self, w: impl io::Write) -> io::Result<()> {
+        self.encoding.write(&self.buf, self.bom, w)
+    }
+
+    /// Writes an event, e.g. from a [`Reader`], to this document. See
+    /// [`Reader`] for an example.
+    ///
+    /// The events have to form balanced elements, i.e. every `StartTag` has
+    /// to be followed by a matching `End` eventually, and `Attr` events have
+    /// to directly follow `StartTag` or other `Attr` events. Events from a
+    /// [`Reader`] always satisfy this.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the events are not balanced as described above, i.e. if an
+    /// `End` does not match the last `StartTag` written via this method, or
+    /// if an `Attr` appears outside of a start tag. Also panics for invalid
+    /// element or attribute names, comments containing `--` or ending with
+    /// `-`, and processing instructions with an invalid target, the target
+    /// `xml` (in any case) or data containing `?>`.
+    pub fn write_event(&mut self, event: Event<'_>) {
+        if self.in_start_tag && !matches!(event, Event::Attr { .. } | Event::End(_)) {
+            self.close_start_tag();
+        }
+
+        match event {
+            Event::StartTag(name) => {
+                if !is_name(name) {
+                    panic!("element name '{name}' is not a valid XML name");
+                }
+                self.event_elems.push(' ');
+                self.event_elems.push_str(name);
+                self.open_tag(name);
+            }
+            Event::Attr { name, value } => {
+                assert!(self.in_start_tag, "attribute event outside of start tag");
+                if !is_name(name) {
+                    panic!("attribute name '{name}' is not a valid XML name");
+                }
+                self.attr(name, &value.unescape());
+            }
+            Event::Text(text) => self.text(&text.unescape()),
+            Event::End(name) => {
+                let start = self.event_elems.rfind(' ')
+                    .unwrap_or_else(|| panic!("end event '{name}' without start tag event"));
+                let open = &self.event_elems[start + 1..];
+                if open != name {
+                    panic!("end event '{name}' does not match start tag event '{open}'");
+                }
+                self.event_elems.truncate(start);
+                if self.in_start_tag {
+                    self.close_empty_elem_tag();
+                } else {
+                    self.end_tag(name);
+                }
+            }
+            Event::Comment(comment) => self.comment(comment),
+            Event::Pi { target, data } => self.pi(target, data),
+        }
+    }
+
     // ----- Private -----
 
     #[doc(hidden)]
-    pub fn new(version: Version, standalone: Option<bool>, format: Format) -> Self {
-        let version = match version {
+    pub fn new(
+        version: Version,
+        encoding: Encoding,
+        standalone: Option<bool>,
+        format: Format,
+        char_policy: CharPolicy,
+        bom: bool,
+    ) -> Self {
+        let version_str = match version {
             Version::V1_0 => "1.0",
             Version::V1_1 => "1.1",
         };
@@ -316,13 +776,31 @@ impl Document {
         // likely be added more to the string, so 64 seems like a good starting
         // point.
         let mut buf = String::with_capacity(64);
-        wr!(buf, r#"<?xml version="{version}" encoding="UTF-8""#);
+        let q = format.quote.char();
+        let encoding_str = encoding.name();
+        wr!(buf, "<?xml version={q}{version_str}{q} encoding={q}{encoding_str}{q}");
         if let Some(standalone) = standalone {
-            wr!(buf, " standalone={}", if standalone { "yes" } else { "no" });
+            let v = if standalone { "yes" } else { "no" };
+            wr!(buf, " standalone={q}{v}{q}");
         }
         wr!(buf, "?>");
 
-        let mut out = Self { buf, format, depth: 0 };
+        let mut out = Self {
+            buf,
+            format,
+            depth: 0,
+            version,
+            encoding,
+            bom,
+            char_policy,
+            in_start_tag: false,
+            pending_newline: false,
+            mixed_depth: None,
+            content_start: 0,
+            after_root: false,
+            tag: StartTag::default(),
+            event_elems: String::new(),
+        };
         out.newline();
         out
     }
@@ -330,14 +808,40 @@ impl Document {
 
     #[doc(hidden)]
     pub fn open_tag(&mut self, name: &str) {
-        wr!(self.buf, "<{name}");
+        self.write_pending_newline();
+        self.tag.name.clear();
+        self.tag.name.push_str(name);
+        self.tag.text.clear();
+        self.tag.attrs.clear();
+        self.in_start_tag = true;
     }
 
     #[doc(hidden)]
     pub fn attr(&mut self, name: &str, value: &dyn fmt::Display) {
-        wr!(self.buf, r#" {name}=""#);
-        escape_into(&mut self.buf, value, true);
-        self.buf.push('"');
+        let name_start = self.tag.text.len();
+        self.tag.text.push_str(name);
+        self.attr_value(name_start, value);
+    }
+
+    #[doc(hidden)]
+    pub fn typed_attr(&mut self, name: &str, value: &dyn AttrValue) {
+        if !value.is_omitted() {
+            self.attr(name, &attr::Formatted(value));
+        }
+    }
+
+    #[doc(hidden)]
+    pub fn opt_attr(&mut self, name: &str, value: attr::OptionalValue<'_>) {
+        match value {
+            attr::OptionalValue::Omit => {}
+            attr::OptionalValue::Flag => match self.format.bool_attrs {
+                BoolAttrs::Name => self.attr(name, &name),
+                BoolAttrs::True => self.attr(name, &"true"),
+                BoolAttrs::Empty => self.attr(name, &""),
+            },
+            attr::OptionalValue::Value(v) => self.typed_attr(name, v),
+            attr::OptionalValue::Display(v) => self.attr(name, v),
+        }
     }
 
     #[doc(hidden)]
@@ -349,29 +853,78 @@ impl Document {
     {
         for (name, value) in attrs {
             // To check whether the name is valid, we first just write it to the
-            // buffer to avoid temporary heap allocations.
-            let len_before = self.buf.len();
-            wr!(self.buf, r#" {name}=""#);
-            let written_name = &self.buf[len_before + 1..self.buf.len() - 2];
+            // start tag buffer to avoid temporary heap allocations.
+            let name_start = self.tag.text.len();
+            wr!(self.tag.text, "{name}");
+            let written_name = &self.tag.text[name_start..];
             if !is_name(written_name) {
                 panic!("attribute name '{written_name}' is not a valid XML name");
             }
+            self.attr_value(name_start, &value);
+        }
+    }
+
+    /// Writes the escaped `value` of the attribute whose name was just
+    /// written to the start tag buffer at `name_start`.
+    fn attr_value(&mut self, name_start: usize, value: &dyn fmt::Display) {
+        let value_start = self.tag.text.len();
+        self.escape(value, true);
+        let attr = TagAttr {
+            name: name_start..value_start,
+            value: value_start..self.tag.text.len(),
+        };
+        if &self.tag.text[attr.name.clone()] == "xml:space"
+            && &self.tag.text[attr.value.clone()] == "preserve"
+        {
+            self.preserve_space();
+        }
+        self.tag.attrs.push(attr);
+    }
+
+    /// Makes sure no whitespace is added inside the current element (in
+    /// pretty mode), as if it had `xml:space="preserve"`. The macro emits
+    /// this for elements marked with `#[preserve_space]`.
+    #[doc(hidden)]
+    pub fn preserve_space(&mut self) {
+        self.mixed_content();
+    }
 
-            escape_into(&mut self.buf, &value, true);
-            self.buf.push('"');
+    /// Marks the current element as containing text, so that no whitespace
+    /// is added inside it, not even before the first text (in pretty mode).
+    /// The macro emits this for elements with text children.
+    #[doc(hidden)]
+    pub fn mixed_content(&mut self) {
+        if self.mixed_depth.is_none() {
+            self.mixed_depth = Some(self.depth + 1);
         }
     }
 
     #[doc(hidden)]
     pub fn close_start_tag(&mut self) {
-        self.buf.push('>');
+        self.in_start_tag = false;
+        self.write_start_tag(">");
         self.depth += 1;
+        self.content_start = self.buf.len();
         self.newline();
     }
 
     #[doc(hidden)]
     pub fn close_empty_elem_tag(&mut self) {
-        self.buf.push_str(if matches!(self.format, Format::Terse) { "/>" } else { " />" });
+        if self.format.empty_elements == EmptyElements::Expand {
+            // The name for the end tag is still in the start tag buffer.
+            self.close_start_tag();
+            let name = std::mem::take(&mut self.tag.name);
+            self.end_tag(&name);
+            self.tag.name = name;
+            return;
+        }
+
+        if self.mixed_depth == Some(self.depth + 1) {
+            self.mixed_depth = None;
+        }
+        self.in_start_tag = false;
+        self.write_start_tag(self.empty_elem_end());
+        self.after_root = self.depth == 0;
         self.newline();
     }
 
@@ -379,27 +932,195 @@ impl Document {
     pub fn end_tag(&mut self, name: &str) {
         assert!(self.depth > 0);
 
-        if let Format::Pretty { indentation } = self.format {
-            assert!(self.buf.ends_with(indentation));
-            self.buf.truncate(self.buf.len() - indentation.len());
+        if self.mixed_depth == Some(self.depth) {
+            self.mixed_depth = None;
         }
         self.depth -= 1;
+
+        // Elements without content are written as `<foo></foo>`, or turned
+        // into `<foo/>` by removing the `>`.
+        if self.buf.len() == self.content_start {
+            self.pending_newline = false;
+            if self.format.empty_elements == EmptyElements::Collapse {
+                self.buf.pop();
+                self.buf.push_str(self.empty_elem_end());
+                self.after_root = self.depth == 0;
+                self.newline();
+                return;
+            }
+        }
+        self.write_pending_newline();
         wr!(self.buf, "</{name}>");
+        self.after_root = self.depth == 0;
         self.newline();
     }
 
     #[doc(hidden)]
     pub fn text(&mut self, text: &dyn fmt::Display) {
-        escape_into(&mut self.buf, text, false);
+        // No whitespace must be added next to text, so we drop the pending
+        // newline and don't add any more in this element.
+        self.pending_newline = false;
+        if self.mixed_depth.is_none() {
+            self.mixed_depth = Some(self.depth);
+        }
+        self.escape(text, false);
+    }
+
+    fn empty_elem_end(&self) -> &'static str {
+        if self.format.space_before_slash { " />" } else { "/>" }
+    }
+
+    /// Writes the current start tag to the buffer, followed by `end`, with
+    /// the attribute order, line wrapping and quote characters of the format.
+    fn write_start_tag(&mut self, end: &str) {
+        let tag = &mut self.tag;
+        if self.format.attr_order == AttrOrder::Sorted {
+            // The value position is the tie breaker for duplicate names, which
+            // keeps them in source order.
+            let text = &tag.text;
+            tag.attrs.sort_unstable_by_key(|a| (&text[a.name.clone()], a.value.start));
+        }
+
+        let wrap = self.attr_wrap(end);
+        let tag = &self.tag;
+        wr!(self.buf, "<{}", tag.name);
+        for (i, attr) in tag.attrs.iter().enumerate() {
+            match (wrap, &self.format.indentation) {
+                (Some((depth, spaces)), Some(indentation)) if i > 0 => {
+                    self.buf.push_str(self.format.newline.as_str());
+                    for _ in 0..depth {
+                        self.buf.push_str(indentation);
+                    }
+                    wr!(self.buf, "{:spaces$}", "");
+                }
+                _ => self.buf.push(' '),
+            }
+
+            let value = &tag.text[attr.value.clone()];
+            let q = self.format.quote.char_for(value);
+            let escaped_q = if q == '"' { "&quot;" } else { "&apos;" };
+            wr!(self.buf, "{}={q}", &tag.text[attr.name.clone()]);
+            for (i, part) in value.split(q).enumerate() {
+                if i > 0 {
+                    self.buf.push_str(escaped_q);
+                }
+                self.buf.push_str(part);
+            }
+            self.buf.push(q);
+        }
+        self.buf.push_str(end);
+    }
+
+    /// If the current start tag (without the final `end`) would exceed the
+    /// `max_width` of the format, returns how to indent every attribute but
+    /// the first on its own line, aligned with the first one: the number of
+    /// indentations and spaces.
+    fn attr_wrap(&self, end: &str) -> Option<(u32, usize)> {
+        let max_width = self.format.max_width?;
+        self.format.indentation.as_ref()?;
+        let tag = &self.tag;
+        if tag.attrs.len() < 2 {
+            return None;
+        }
+
+        let line_start = self.buf.rfind('\n').map_or(0, |p| p + 1);
+        let prefix = &self.buf[line_start..];
+        let attrs_width: usize = tag.attrs.iter().map(|attr| {
+            let value = &tag.text[attr.value.clone()];
+            let q = self.format.quote.char_for(value);
+            // ` name=""`, with quotes in the value written as 6 char references.
+            let name_width = tag.text[attr.name.clone()].chars().count();
+            name_width + 4 + value.chars().count() + 5 * value.matches(q).count()
+        }).sum();
+        let width = prefix.chars().count()
+            + 1 + tag.name.chars().count()
+            + attrs_width
+            + end.chars().count();
+        if width <= max_width {
+            return None;
+        }
+
+        // The new lines start with the same indentation as the tag (unless
+        // the tag is preceded by text), followed by spaces up to the first
+        // attribute.
+        let (depth, spaces) = if prefix.trim_start().is_empty() {
+            (self.depth, 0)
+        } else {
+            (0, prefix.chars().count())
+        };
+        Some((depth, spaces + tag.name.chars().count() + 2))
+    }
+
+    /// Writes a comment. Panics if `comment` contains `--` or ends with `-`.
+    fn comment(&mut self, comment: &str) {
+        if comment.contains("--") || comment.ends_with('-') {
+            panic!("comment '{comment}' contains '--' or ends with '-'");
+        }
+        self.write_pending_newline();
+        wr!(self.buf, "<!--{comment}-->");
+        self.newline();
+    }
+
+    /// Writes a processing instruction. Panics if `target` is not a valid
+    /// target or `data` contains `?>`.
+    fn pi(&mut self, target: &str, data: &str) {
+        if !is_name(target) || target.eq_ignore_ascii_case("xml") {
+            panic!("'{target}' is not a valid processing instruction target");
+        }
+        if data.contains("?>") {
+            panic!("processing instruction data '{data}' contains '?>'");
+        }
+        self.write_pending_newline();
+        wr!(self.buf, "<?{target}");
+        if !data.is_empty() {
+            wr!(self.buf, " {data}");
+        }
+        self.buf.push_str("?>");
         self.newline();
     }
 
-    /// Appends a newline and proper indentation according to `self.depth` to
-    /// the buffer.
+    /// Writes the escaped `v` into the buffer, or into the start tag buffer
+    /// for attribute values. We do that without temporary heap allocations
+    /// via `EscapedWriter`, which is a layer between the `fmt::Display` logic
+    /// of `v` and our final buffer.
+    fn escape(&mut self, v: &dyn fmt::Display, in_attr: bool) {
+        let mut writer = EscapedWriter {
+            buf: if in_attr { &mut self.tag.text } else { &mut self.buf },
+            in_attr,
+            ascii_only: self.format.ascii_only,
+            encoding: self.encoding,
+            carriage_returns: self.format.carriage_returns,
+            after_cr: false,
+            version: self.version,
+            char_policy: self.char_policy,
+        };
+        wr!(writer, "{}", v);
+    }
+
+    /// Requests a newline with proper indentation in pretty mode. It is
+    /// written lazily by `write_pending_newline`, except outside of the root
+    /// element, where no text can follow.
     fn newline(&mut self) {
-        if let Format::Pretty { indentation } = self.format {
-            self.buf.reserve(1 + indentation.len() * self.depth as usize);
-            self.buf.push('\n');
+        if self.after_root && self.format.trailing_newline {
+            self.buf.push_str(self.format.newline.as_str());
+        } else if self.format.indentation.is_some() && self.mixed_depth.is_none() {
+            self.pending_newline = true;
+            // After the root element, we don't know whether anything else
+            // follows, so we only write the newline once something does.
+            if self.depth == 0 && !self.after_root {
+                self.write_pending_newline();
+            }
+        }
+    }
+
+    /// Appends a newline and indentation according to `self.depth` to the
+    /// buffer, if one was requested via `newline`.
+    fn write_pending_newline(&mut self) {
+        if !std::mem::take(&mut self.pending_newline) {
+            return;
+        }
+        if let Some(indentation) = &self.format.indentation {
+            self.buf.push_str(self.format.newline.as_str());
             for _ in 0..self.depth {
                 self.buf.push_str(indentation);
             }
@@ -409,75 +1130,165 @@ impl Document {
 
 
 #[doc(hidden)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Version {
     V1_0,
     V1_1,
 }
 
-/// Specifies how the XML should be formatted.
+/// Specifies what to do with characters in text or attribute values that are
+/// not allowed in XML documents.
+///
+/// Examples are most control characters like U+0000 or U+001B, and the
+/// non-characters U+FFFE and U+FFFF. Which characters are allowed depends on
+/// the XML version specified in the prolog: XML 1.1 allows most control
+/// characters as character references (e.g. `&#x1B;`), so in that case they
+/// are written as such and this policy only applies to the remaining ones.
+/// See [`xml`] for more information on XML 1.1.
 ///
 /// Pass to [`xml`] like this:
 ///
 /// ```
-/// use ogrim::{xml, Format};
+/// use ogrim::{xml, CharPolicy};
 ///
 /// let doc = xml!(
-///     #[format = Format::Pretty { indentation: "  " }]
+///     #[char_policy = CharPolicy::Strip]
 ///     <?xml version="1.0" ?>
-///     <foo></>
+///     <foo>{"bell: \u{7}"}</>
 /// );
+///
+/// assert_eq!(doc.as_str(), r#"<?xml version="1.0" encoding="UTF-8"?><foo>bell: </foo>"#);
 /// ```
 ///
-/// After `format = ` you can pass any Rust expression, also referencing
-/// variables, for example to make formatting conditional. If not specified,
-/// terse formatting is used.
-pub enum Format {
-    /// Minimized, as short as possible.
-    Terse,
-
-    /// Pretty printed for human consumption.
-    Pretty {
-        /// String with which to indent, e.g. `"  "`.
-        indentation: &'static str,
-    },
-}
+/// If not specified, [`CharPolicy::Replace`] is used.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CharPolicy {
+    /// Replace the character with U+FFFD (REPLACEMENT CHARACTER), written as
+    /// character reference if non-ASCII characters are or if the encoding
+    /// can't represent it.
+    #[default]
+    Replace,
 
+    /// Remove the character from the output.
+    Strip,
 
-/// Writes the escaped `v` into `buf`. We do that without temporary heap
-/// allocations via `EscapedWriter`, which is a layer between the
-/// `fmt::Display` logic of `v` and our final buffer.
-fn escape_into(buf: &mut String, v: &dyn fmt::Display, escape_quote: bool) {
-    wr!(EscapedWriter { buf, escape_quote }, "{}", v);
+    /// Panic (like writing invalid attribute names does).
+    Panic,
 }
 
 struct EscapedWriter<'a> {
     buf: &'a mut String,
-    escape_quote: bool,
+    /// Whether an attribute value is written. Quotes are not escaped here,
+    /// but when writing the start tag.
+    in_attr: bool,
+    /// Whether to write non-ASCII characters as references.
+    ascii_only: bool,
+    /// Characters not representable in this encoding are written as
+    /// references.
+    encoding: Encoding,
+    /// What to do with `\r` in text.
+    carriage_returns: CarriageReturns,
+    /// Whether the last chunk ended with `\r` that was normalized to `\n`,
+    /// so that a `\n` at the start of the next chunk has to be dropped.
+    after_cr: bool,
+    version: Version,
+    char_policy: CharPolicy,
 }
 
 impl fmt::Write for EscapedWriter<'_> {
     fn write_str(&mut self, s: &str) -> fmt::Result {
-        // We always use `"` to quote attribute values, so we don't need to
-        // escape `'`. `>` does not necessarily need to be escaped, but it is
-        // strongly recommended.
-        let escape_quote = self.escape_quote;
-        let needs_escape = |c: char| matches!(c, '<' | '>' | '&') || (escape_quote && c == '"');
+        // `>` does not necessarily need to be escaped, but it is strongly
+        // recommended.
+        let in_attr = self.in_attr;
+        let version = self.version;
+        let ascii_only = self.ascii_only;
+        let encoding = self.encoding;
+        let escape_cr = self.carriage_returns != CarriageReturns::Keep;
+        let needs_escape = |c: char| {
+            matches!(c, '<' | '>' | '&')
+                || (in_attr && matches!(c, '\t' | '\n' | '\r'))
+                || (escape_cr && c == '\r')
+                || (ascii_only && !c.is_ascii())
+                || !encoding.can_encode(c)
+                || (c < ' ' && !matches!(c, '\t' | '\n' | '\r'))
+                || matches!(c, '\u{FFFE}' | '\u{FFFF}')
+                || (version == Version::V1_1 && needs_char_ref_1_1(c))
+        };
 
         let mut remaining = s;
+        if self.after_cr && !s.is_empty() {
+            self.after_cr = false;
+            remaining = remaining.strip_prefix('\n').unwrap_or(remaining);
+        }
+
         while let Some(pos) = remaining.find(needs_escape) {
             self.buf.push_str(&remaining[..pos]);
-            match remaining.as_bytes()[pos] {
-                b'<' => self.buf.push_str("&lt;"),
-                b'>' => self.buf.push_str("&gt;"),
-                b'&' => self.buf.push_str("&amp;"),
-                b'"' => self.buf.push_str("&quot;"),
-                _ => unreachable!(),
+            let c = remaining[pos..].chars().next().unwrap();
+            let mut len = c.len_utf8();
+            match c {
+                '<' => self.buf.push_str("&lt;"),
+                '>' => self.buf.push_str("&gt;"),
+                '&' => self.buf.push_str("&amp;"),
+                // Only escaped in attribute values, where they would be
+                // normalized to spaces otherwise.
+                '\t' => self.buf.push_str("&#x9;"),
+                '\n' => self.buf.push_str("&#xA;"),
+                '\r' if self.in_attr
+                    || self.carriage_returns == CarriageReturns::Escape =>
+                {
+                    self.buf.push_str("&#xD;");
+                }
+                '\r' => {
+                    // `CarriageReturns::Normalize`
+                    self.buf.push('\n');
+                    match remaining[pos + 1..].chars().next() {
+                        Some('\n') => len += 1,
+                        None => self.after_cr = true,
+                        Some(_) => {}
+                    }
+                }
+                c if self.version == Version::V1_1 && needs_char_ref_1_1(c) => {
+                    wr!(self.buf, "&#x{:X};", c as u32);
+                }
+                c if ((self.ascii_only && !c.is_ascii()) || !self.encoding.can_encode(c))
+                    && !matches!(c, '\u{FFFE}' | '\u{FFFF}') =>
+                {
+                    wr!(self.buf, "&#x{:X};", c as u32);
+                }
+                _ => match self.char_policy {
+                    CharPolicy::Replace
+                        if self.ascii_only || !self.encoding.can_encode('\u{FFFD}') =>
+                    {
+                        self.buf.push_str("&#xFFFD;");
+                    }
+                    CharPolicy::Replace => self.buf.push('\u{FFFD}'),
+                    CharPolicy::Strip => {}
+                    CharPolicy::Panic => panic!(
+                        "character U+{:04X} is not allowed in XML {} documents",
+                        c as u32,
+                        if self.version == Version::V1_0 { "1.0" } else { "1.1" },
+                    ),
+                },
             }
-            remaining = &remaining[pos + 1..];
+            remaining = &remaining[pos + len..];
         }
         self.buf.push_str(remaining);
         Ok(())
     }
 }
 
+/// Returns whether `c` has to be written as character reference in XML 1.1
+/// documents. These are the "restricted characters", which are only allowed
+/// as references, plus NEL and LSEP, which parsers would otherwise normalize
+/// to `\n` as XML 1.1 treats them as line endings.
+fn needs_char_ref_1_1(c: char) -> bool {
+    matches!(c,
+        '\u{1}'..='\u{8}'
+        | '\u{B}'..='\u{C}'
+        | '\u{E}'..='\u{1F}'
+        | '\u{7F}'..='\u{9F}'
+        | '\u{2028}'
+    )
+}
+
 include!("shared.rs");
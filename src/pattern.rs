@@ -0,0 +1,176 @@
+use std::{borrow::Cow, fmt, str::FromStr};
+
+use crate::{Escaped, Event, ParseError, Reader};
+
+
+
+/// Runtime part of `xml_match!`: the macro emits calls to these methods,
+/// walking the pattern from top to bottom.
+#[doc(hidden)]
+pub struct Matcher<'a> {
+    reader: Reader<'a>,
+    /// An event that was read but not yet consumed.
+    peeked: Option<Event<'a>>,
+    /// Attributes and position of the element that was matched last.
+    attrs: Vec<(&'a str, Escaped<'a>)>,
+    elem_pos: usize,
+    /// Names of the pattern elements we are currently in.
+    stack: Vec<&'static str>,
+}
+
+impl<'a> Matcher<'a> {
+    pub fn new(input: &'a str) -> Self {
+        Self {
+            reader: Reader::new(input),
+            peeked: None,
+            attrs: Vec::new(),
+            elem_pos: 0,
+            stack: Vec::new(),
+        }
+    }
+
+    /// Finds the next element with the given name in the current element,
+    /// skipping other elements, and enters it. For the root element, the
+    /// name has to match.
+    pub fn element(&mut self, name: &'static str) -> Result<(), ParseError> {
+        loop {
+            match self.next()? {
+                Event::StartTag(n) if n == name => break,
+                Event::StartTag(n) if self.stack.is_empty() => {
+                    return Err(self.error(format_args!(
+                        "expected root element '{name}', found '{n}'",
+                    )));
+                }
+                Event::StartTag(_) => self.skip_element()?,
+                Event::End(parent) => {
+                    return Err(self.error(format_args!(
+                        "missing element '{name}' in '{parent}'",
+                    )));
+                }
+                _ => {}
+            }
+        }
+
+        self.elem_pos = self.reader.event_pos();
+        self.attrs.clear();
+        loop {
+            match self.next()? {
+                Event::Attr { name, value } => self.attrs.push((name, value)),
+                other => {
+                    self.peeked = Some(other);
+                    break;
+                }
+            }
+        }
+        self.stack.push(name);
+        Ok(())
+    }
+
+    /// Returns the value of the given attribute of the element that was
+    /// entered last.
+    pub fn attr(&self, name: &str) -> Result<Cow<'a, str>, ParseError> {
+        match self.attrs.iter().find(|(n, _)| *n == name) {
+            Some((_, value)) => Ok(value.unescape()),
+            None => Err(self.reader.error_at(self.elem_pos, format!(
+                "missing attribute '{name}' on '{}'",
+                self.stack.last().unwrap(),
+            ))),
+        }
+    }
+
+    pub fn expect_attr(&self, name: &str, expected: &str) -> Result<(), ParseError> {
+        let value = self.attr(name)?;
+        if value != expected {
+            return Err(self.reader.error_at(self.elem_pos, format!(
+                "expected attribute '{name}' on '{}' to be '{expected}', but it is '{value}'",
+                self.stack.last().unwrap(),
+            )));
+        }
+        Ok(())
+    }
+
+    /// Reads the text content of the current element and leaves it. Comments
+    /// and processing instructions are ignored, child elements are an error.
+    pub fn text(&mut self) -> Result<Cow<'a, str>, ParseError> {
+        let mut out = Cow::Borrowed("");
+        loop {
+            match self.next()? {
+                Event::Text(text) if out.is_empty() => out = text.unescape(),
+                Event::Text(text) => out.to_mut().push_str(&text.unescape()),
+                Event::StartTag(n) => {
+                    return Err(self.error(format_args!(
+                        "expected only text in '{}', found element '{n}'",
+                        self.stack.last().unwrap(),
+                    )));
+                }
+                Event::End(_) => break,
+                _ => {}
+            }
+        }
+        self.stack.pop();
+        Ok(out)
+    }
+
+    pub fn expect_text(&mut self, expected: &str) -> Result<(), ParseError> {
+        let elem = *self.stack.last().unwrap();
+        let text = self.text()?;
+        if text != expected {
+            return Err(self.error(format_args!(
+                "expected text '{expected}' in '{elem}', found '{text}'",
+            )));
+        }
+        Ok(())
+    }
+
+    /// Leaves the current element, skipping all remaining content.
+    pub fn end(&mut self) -> Result<(), ParseError> {
+        self.skip_element()?;
+        self.stack.pop();
+        Ok(())
+    }
+
+    /// Converts a bound value to the type of the binding.
+    pub fn convert<T>(&self, name: &str, value: Cow<'a, str>) -> Result<T, ParseError>
+    where
+        T: FromStr,
+        T::Err: fmt::Display,
+    {
+        value.parse().map_err(|e| self.error(format_args!(
+            "invalid value '{value}' for '{name}': {e}",
+        )))
+    }
+
+    /// Checks that the rest of the document is well-formed.
+    pub fn finish(mut self) -> Result<(), ParseError> {
+        while self.reader.next_event()?.is_some() {}
+        Ok(())
+    }
+
+    /// Skips events until the end of the element whose start tag was read
+    /// last.
+    fn skip_element(&mut self) -> Result<(), ParseError> {
+        let mut depth = 1;
+        while depth > 0 {
+            match self.next()? {
+                Event::StartTag(_) => depth += 1,
+                Event::End(_) => depth -= 1,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn next(&mut self) -> Result<Event<'a>, ParseError> {
+        if let Some(event) = self.peeked.take() {
+            return Ok(event);
+        }
+        match self.reader.next_event()? {
+            Some(event) => Ok(event),
+            None => Err(self.error(format_args!("unexpected end of document"))),
+        }
+    }
+
+    fn error(&self, msg: fmt::Arguments<'_>) -> ParseError {
+        self.reader.error_at(self.reader.event_pos(), msg.to_string())
+    }
+}
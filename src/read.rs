@@ -0,0 +1,704 @@
+//! Pull parser for reading XML documents.
+
+use std::{borrow::Cow, fmt};
+
+use crate::{is_name_char, is_name_start_char};
+
+
+
+/// Error returned by [`Reader`] and [`check`][crate::check], describing the
+/// first problem found in the input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    line: usize,
+    column: usize,
+    msg: String,
+}
+
+impl ParseError {
+    /// The line of the problem, starting at 1.
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// The column (in `char`s) of the problem, starting at 1.
+    pub fn column(&self) -> usize {
+        self.column
+    }
+
+    /// Description of the problem, without position.
+    pub fn message(&self) -> &str {
+        &self.msg
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.msg)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+
+
+/// A piece of an XML document, as returned by [`Reader`].
+///
+/// All strings borrow from the input. Text and attribute values are
+/// [`Escaped`] and only unescaped when you ask for it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event<'a> {
+    /// Start of an element with the given name. Followed by `Attr` events for
+    /// all its attributes, then its content and finally an `End` event. For
+    /// `<foo/>`, the `End` event directly follows the attributes.
+    StartTag(&'a str),
+
+    /// An attribute of the element started by the last `StartTag` event.
+    Attr {
+        name: &'a str,
+        value: Escaped<'a>,
+    },
+
+    /// Text content, including whitespace between tags and CDATA sections.
+    /// Adjacent text might be split into several events.
+    Text(Escaped<'a>),
+
+    /// End of the element with the given name.
+    End(&'a str),
+
+    /// A comment, without `<!--` and `-->`.
+    Comment(&'a str),
+
+    /// A processing instruction, e.g. `<?target data?>`.
+    Pi {
+        target: &'a str,
+        data: &'a str,
+    },
+}
+
+/// Text or attribute value as it appears in the input, i.e. with references
+/// like `&lt;` not yet replaced.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Escaped<'a> {
+    raw: &'a str,
+    kind: EscapedKind,
+    v1_1: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EscapedKind {
+    Text,
+    CData,
+    AttrValue,
+}
+
+impl<'a> Escaped<'a> {
+    /// Returns the string as it appears in the input (without quotes or
+    /// CDATA markers).
+    pub fn raw(&self) -> &'a str {
+        self.raw
+    }
+
+    /// Returns whether this is the content of a CDATA section.
+    pub fn is_cdata(&self) -> bool {
+        self.kind == EscapedKind::CData
+    }
+
+    /// Returns the actual value, with references replaced and line endings
+    /// (and, in attribute values, whitespace) normalized like XML parsers
+    /// are required to. Only allocates if anything needs to be replaced.
+    pub fn unescape(&self) -> Cow<'a, str> {
+        let special = |c: char| match c {
+            '&' => self.kind != EscapedKind::CData,
+            '\r' => true,
+            '\t' | '\n' => self.kind == EscapedKind::AttrValue,
+            '\u{85}' | '\u{2028}' => self.v1_1,
+            _ => false,
+        };
+        let Some(first) = self.raw.find(special) else {
+            return Cow::Borrowed(self.raw);
+        };
+
+        let newline = if self.kind == EscapedKind::AttrValue { ' ' } else { '\n' };
+        let mut out = String::with_capacity(self.raw.len());
+        out.push_str(&self.raw[..first]);
+        let mut remaining = &self.raw[first..];
+        while let Some(pos) = remaining.find(special) {
+            out.push_str(&remaining[..pos]);
+            remaining = &remaining[pos..];
+            let c = remaining.chars().next().unwrap();
+            remaining = &remaining[c.len_utf8()..];
+            match c {
+                '&' => {
+                    // References have been checked by the reader already.
+                    let end = remaining.find(';').unwrap();
+                    out.push(unescape_reference(&remaining[..end]));
+                    remaining = &remaining[end + 1..];
+                }
+                '\r' => {
+                    out.push(newline);
+                    if let Some(rest) = remaining.strip_prefix('\n') {
+                        remaining = rest;
+                    } else if let Some(rest) = remaining.strip_prefix('\u{85}').filter(|_| self.v1_1) {
+                        remaining = rest;
+                    }
+                }
+                '\t' => out.push(' '),
+                _ => out.push(newline),
+            }
+        }
+        out.push_str(remaining);
+        Cow::Owned(out)
+    }
+}
+
+/// Returns the character the reference `&{reference};` refers to.
+fn unescape_reference(reference: &str) -> char {
+    match reference {
+        "lt" => '<',
+        "gt" => '>',
+        "amp" => '&',
+        "apos" => '\'',
+        "quot" => '"',
+        _ => {
+            let num = &reference[1..];
+            let n = match num.strip_prefix('x') {
+                Some(hex) => u32::from_str_radix(hex, 16),
+                None => num.parse(),
+            };
+            char::from_u32(n.unwrap()).unwrap()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// Before the root element. `Start` is only at the very start of the
+    /// input, where the XML declaration can appear.
+    Start,
+    Prolog { seen_doctype: bool },
+    StartTag,
+    Content,
+    Epilog,
+}
+
+/// Minimal XML pull parser, splitting a document into [`Event`]s.
+///
+/// The reader checks well-formedness (see [`check`][crate::check]) while
+/// parsing, and stops with an error once it finds a problem. Except for a
+/// stack of open element names and the attribute names of the current start
+/// tag, it does not allocate: the events borrow from the input. The XML
+/// declaration and whitespace outside the root element do not produce
+/// events; DTDs are skipped.
+///
+/// ```
+/// use ogrim::{Event, Reader};
+///
+/// let input = r#"<feed><entry id="1">Fox &amp; friends</entry></feed>"#;
+/// let mut titles = vec![];
+/// let mut reader = Reader::new(input);
+/// while let Some(event) = reader.next_event()? {
+///     if let Event::Text(text) = event {
+///         titles.push(text.unescape());
+///     }
+/// }
+/// assert_eq!(titles, ["Fox & friends"]);
+/// # Ok::<_, ogrim::ParseError>(())
+/// ```
+///
+/// `Reader` is also an iterator over `Result<Event, ParseError>`, which
+/// stops after the first error. Events can be written to a
+/// [`Document`][crate::Document] via
+/// [`Document::write_event`][crate::Document::write_event], for example to
+/// embed or filter parsed XML:
+///
+/// ```
+/// use ogrim::{xml, Event, Reader};
+///
+/// let input = "<items><item>a</item><secret/><item>b</item></items>";
+/// let doc = xml!(
+///     <?xml version="1.0" ?>
+///     <copy>
+///         {|doc| for event in Reader::new(input) {
+///             match event? {
+///                 Event::StartTag("secret") | Event::End("secret") => {}
+///                 event => doc.write_event(event),
+///             }
+///         }}
+///     </copy>
+/// );
+///
+/// assert_eq!(doc.as_str(), concat!(
+///     r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+///     "<copy><items><item>a</item><item>b</item></items></copy>",
+/// ));
+/// # Ok::<_, ogrim::ParseError>(())
+/// ```
+pub struct Reader<'a> {
+    input: &'a str,
+    pos: usize,
+    state: State,
+    failed: bool,
+    v1_1: bool,
+    stack: Vec<&'a str>,
+    /// Names of the attributes of the current start tag.
+    attrs: Vec<&'a str>,
+    /// Start position of the last returned event.
+    event_pos: usize,
+}
+
+macro_rules! bail {
+    ($self:ident, $pos:expr, $($t:tt)*) => {
+        return Err($self.error_at($pos, format!($($t)*)))
+    };
+}
+
+impl<'a> Reader<'a> {
+    /// Creates a reader for the given document. A leading byte order mark is
+    /// skipped.
+    pub fn new(input: &'a str) -> Self {
+        Self {
+            pos: if input.starts_with('\u{FEFF}') { 3 } else { 0 },
+            input,
+            state: State::Start,
+            failed: false,
+            v1_1: false,
+            stack: Vec::new(),
+            attrs: Vec::new(),
+            event_pos: 0,
+        }
+    }
+
+    /// Returns the next event or `None` if the end of the document was
+    /// reached.
+    pub fn next_event(&mut self) -> Result<Option<Event<'a>>, ParseError> {
+        loop {
+            match self.state {
+                State::Start => {
+                    if self.rest().starts_with("<?xml")
+                        && self.rest()[5..].starts_with(['?', ' ', '\t', '\r', '\n'])
+                    {
+                        self.xml_decl()?;
+                    }
+                    self.state = State::Prolog { seen_doctype: false };
+                }
+                State::Prolog { seen_doctype } => {
+                    self.skip_whitespace();
+                    let rest = self.rest();
+                    if rest.is_empty() {
+                        bail!(self, self.pos, "missing root element");
+                    } else if rest.starts_with("<!DOCTYPE") {
+                        if seen_doctype {
+                            bail!(self, self.pos, "duplicate document type declaration");
+                        }
+                        self.doctype()?;
+                        self.state = State::Prolog { seen_doctype: true };
+                    } else if let Some(token) = self.misc()? {
+                        return Ok(Some(token));
+                    } else if rest.starts_with('<') && !rest.starts_with("<!") {
+                        return self.start_tag().map(Some);
+                    } else {
+                        bail!(self, self.pos, "expected root element");
+                    }
+                }
+                State::StartTag => return self.attr_or_end().map(Some),
+                State::Content => return self.content().map(Some),
+                State::Epilog => {
+                    self.skip_whitespace();
+                    if self.rest().is_empty() {
+                        return Ok(None);
+                    }
+                    match self.misc()? {
+                        Some(token) => return Ok(Some(token)),
+                        None if self.rest().starts_with('<') => {
+                            bail!(self, self.pos, "only one root element is allowed");
+                        }
+                        None => {
+                            bail!(self, self.pos, "text is not allowed outside the root element");
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Parses a comment or PI, if the input continues with one.
+    fn misc(&mut self) -> Result<Option<Event<'a>>, ParseError> {
+        let rest = self.rest();
+        if rest.starts_with("<!--") {
+            self.comment().map(Some)
+        } else if rest.starts_with("<?") {
+            self.pi().map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn xml_decl(&mut self) -> Result<(), ParseError> {
+        self.pos += "<?xml".len();
+        let version = self.decl_attr("version", true)?.unwrap();
+        match version {
+            "1.0" => {}
+            "1.1" => self.v1_1 = true,
+            _ => bail!(self, self.pos - version.len() - 1, "unsupported XML version '{version}'"),
+        }
+        if let Some(encoding) = self.decl_attr("encoding", false)? {
+            let valid = encoding.starts_with(|c: char| c.is_ascii_alphabetic())
+                && encoding.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'));
+            if !valid {
+                bail!(self, self.pos - encoding.len() - 1, "invalid encoding name '{encoding}'");
+            }
+        }
+        if let Some(standalone) = self.decl_attr("standalone", false)? {
+            if standalone != "yes" && standalone != "no" {
+                bail!(self, self.pos - standalone.len() - 1, "'standalone' must be 'yes' or 'no'");
+            }
+        }
+        self.skip_whitespace();
+        self.expect("?>")
+    }
+
+    /// Parses ` name="value"` inside the XML declaration.
+    fn decl_attr(&mut self, name: &str, required: bool) -> Result<Option<&'a str>, ParseError> {
+        let before = self.pos;
+        let had_space = self.skip_whitespace();
+        if !self.rest().starts_with(name) {
+            self.pos = before;
+            if required {
+                bail!(self, self.pos, "expected '{name}' in XML declaration");
+            }
+            return Ok(None);
+        }
+        if !had_space {
+            bail!(self, self.pos, "expected whitespace");
+        }
+        self.pos += name.len();
+        self.eq()?;
+        self.quoted().map(Some)
+    }
+
+    /// Skips the document type declaration. Only its rough structure is
+    /// checked.
+    fn doctype(&mut self) -> Result<(), ParseError> {
+        self.pos += "<!DOCTYPE".len();
+        if !self.skip_whitespace() {
+            bail!(self, self.pos, "expected whitespace");
+        }
+        self.name()?;
+        let mut in_subset = false;
+        loop {
+            let rest = self.rest();
+            match rest.chars().next() {
+                None => bail!(self, self.pos, "unterminated document type declaration"),
+                Some('"' | '\'') => {
+                    self.quoted()?;
+                }
+                Some('[') if !in_subset => {
+                    in_subset = true;
+                    self.pos += 1;
+                }
+                Some(']') if in_subset => {
+                    in_subset = false;
+                    self.pos += 1;
+                }
+                Some('<') if in_subset && rest.starts_with("<!--") => {
+                    self.comment()?;
+                }
+                Some('<') if in_subset && rest.starts_with("<?") => {
+                    self.pi()?;
+                }
+                Some('>') if !in_subset => {
+                    self.pos += 1;
+                    return Ok(());
+                }
+                Some(c) => {
+                    self.check_char(c, self.pos)?;
+                    self.pos += c.len_utf8();
+                }
+            }
+        }
+    }
+
+    fn comment(&mut self) -> Result<Event<'a>, ParseError> {
+        self.event_pos = self.pos;
+        self.pos += "<!--".len();
+        let start = self.pos;
+        let Some(len) = self.rest().find("--") else {
+            bail!(self, start - 4, "unterminated comment");
+        };
+        if !self.rest()[len..].starts_with("-->") {
+            bail!(self, start + len, "'--' is not allowed inside comments");
+        }
+        if self.rest()[..len].ends_with('-') {
+            bail!(self, start + len - 1, "comments must not end with '-'");
+        }
+        let content = &self.rest()[..len];
+        self.check_chars(content, start)?;
+        self.pos += len + "-->".len();
+        Ok(Event::Comment(content))
+    }
+
+    fn pi(&mut self) -> Result<Event<'a>, ParseError> {
+        self.event_pos = self.pos;
+        self.pos += "<?".len();
+        let target_pos = self.pos;
+        let target = self.name()?;
+        if target.eq_ignore_ascii_case("xml") {
+            bail!(self, target_pos, "the XML declaration is only allowed at the start of the document");
+        }
+        let had_space = self.skip_whitespace();
+        let start = self.pos;
+        let Some(len) = self.rest().find("?>") else {
+            bail!(self, target_pos - 2, "unterminated processing instruction");
+        };
+        if len > 0 && !had_space {
+            bail!(self, start, "expected whitespace after processing instruction target");
+        }
+        let data = &self.rest()[..len];
+        self.check_chars(data, start)?;
+        self.pos += len + "?>".len();
+        Ok(Event::Pi { target, data })
+    }
+
+    /// Parses `<name`, assuming the input starts with `<` (not followed by
+    /// `/`, `?` or `!`).
+    fn start_tag(&mut self) -> Result<Event<'a>, ParseError> {
+        self.event_pos = self.pos;
+        self.pos += 1;
+        let name = self.name()?;
+        self.stack.push(name);
+        self.attrs.clear();
+        self.state = State::StartTag;
+        Ok(Event::StartTag(name))
+    }
+
+    /// Inside a start tag: parses the next attribute or the end of the tag.
+    fn attr_or_end(&mut self) -> Result<Event<'a>, ParseError> {
+        let had_space = self.skip_whitespace();
+        self.event_pos = self.pos;
+        if self.rest().starts_with("/>") {
+            self.pos += 2;
+            let name = self.stack.pop().unwrap();
+            self.state = if self.stack.is_empty() { State::Epilog } else { State::Content };
+            return Ok(Event::End(name));
+        }
+        if self.rest().starts_with('>') {
+            self.pos += 1;
+            self.state = State::Content;
+            return self.content();
+        }
+        if self.rest().is_empty() {
+            bail!(self, self.pos, "unterminated start tag");
+        }
+        if !had_space {
+            bail!(self, self.pos, "expected whitespace, '>' or '/>'");
+        }
+
+        let name_pos = self.pos;
+        let name = self.name()?;
+        if self.attrs.contains(&name) {
+            bail!(self, name_pos, "duplicate attribute '{name}'");
+        }
+        self.attrs.push(name);
+        self.eq()?;
+        let value_pos = self.pos + 1;
+        let value = self.quoted()?;
+        if let Some(p) = value.find('<') {
+            bail!(self, value_pos + p, "'<' is not allowed in attribute values");
+        }
+        self.check_text(value, value_pos)?;
+        Ok(Event::Attr {
+            name,
+            value: Escaped { raw: value, kind: EscapedKind::AttrValue, v1_1: self.v1_1 },
+        })
+    }
+
+    /// Parses the next piece of element content.
+    fn content(&mut self) -> Result<Event<'a>, ParseError> {
+        let rest = self.rest();
+        let start = self.pos;
+        self.event_pos = start;
+        if rest.is_empty() {
+            bail!(self, self.pos, "unclosed element '{}'", self.stack.last().unwrap());
+        } else if rest.starts_with("</") {
+            self.pos += 2;
+            let name = self.name()?;
+            let expected = self.stack.pop().unwrap();
+            if name != expected {
+                bail!(self, start, "end tag '{name}' does not match start tag '{expected}'");
+            }
+            self.skip_whitespace();
+            self.expect(">")?;
+            if self.stack.is_empty() {
+                self.state = State::Epilog;
+            }
+            Ok(Event::End(name))
+        } else if rest.starts_with("<![CDATA[") {
+            let Some(len) = rest.find("]]>") else {
+                bail!(self, start, "unterminated CDATA section");
+            };
+            let content = &rest["<![CDATA[".len()..len];
+            self.check_chars(content, start + "<![CDATA[".len())?;
+            self.pos += len + "]]>".len();
+            Ok(Event::Text(Escaped { raw: content, kind: EscapedKind::CData, v1_1: self.v1_1 }))
+        } else if rest.starts_with("<!--") {
+            self.comment()
+        } else if rest.starts_with("<?") {
+            self.pi()
+        } else if rest.starts_with("<!") {
+            bail!(self, start, "expected comment or CDATA section");
+        } else if rest.starts_with('<') {
+            self.start_tag()
+        } else {
+            let len = rest.find('<').unwrap_or(rest.len());
+            let text = &rest[..len];
+            if let Some(p) = text.find("]]>") {
+                bail!(self, start + p, "']]>' is not allowed in text");
+            }
+            self.check_text(text, start)?;
+            self.pos += len;
+            Ok(Event::Text(Escaped { raw: text, kind: EscapedKind::Text, v1_1: self.v1_1 }))
+        }
+    }
+
+    /// Parses a name at the current position.
+    fn name(&mut self) -> Result<&'a str, ParseError> {
+        let rest = self.rest();
+        if !rest.starts_with(is_name_start_char) {
+            match rest.chars().next() {
+                Some(c) => bail!(self, self.pos, "expected name, found '{c}'"),
+                None => bail!(self, self.pos, "expected name, found end of input"),
+            }
+        }
+        let len = rest.find(|c| !is_name_char(c)).unwrap_or(rest.len());
+        self.pos += len;
+        Ok(&rest[..len])
+    }
+
+    /// Parses `S? = S?`.
+    fn eq(&mut self) -> Result<(), ParseError> {
+        self.skip_whitespace();
+        self.expect("=")?;
+        self.skip_whitespace();
+        Ok(())
+    }
+
+    /// Parses a string delimited by `"` or `'` and returns it without
+    /// delimiters.
+    fn quoted(&mut self) -> Result<&'a str, ParseError> {
+        let rest = self.rest();
+        let quote = match rest.chars().next() {
+            Some(q @ ('"' | '\'')) => q,
+            _ => bail!(self, self.pos, "expected quoted value"),
+        };
+        let Some(len) = rest[1..].find(quote) else {
+            bail!(self, self.pos, "unterminated quoted value");
+        };
+        self.pos += len + 2;
+        Ok(&rest[1..len + 1])
+    }
+
+    /// Checks characters and references in text or attribute values.
+    fn check_text(&self, text: &str, offset: usize) -> Result<(), ParseError> {
+        let mut chars = text.char_indices();
+        while let Some((i, c)) = chars.next() {
+            if c != '&' {
+                self.check_char(c, offset + i)?;
+                continue;
+            }
+
+            let Some(len) = text[i..].find(';') else {
+                bail!(self, offset + i, "unterminated reference");
+            };
+            let reference = &text[i + 1..i + len];
+            if let Some(num) = reference.strip_prefix('#') {
+                let parsed = match num.strip_prefix('x') {
+                    Some(hex) if hex.chars().all(|c| c.is_ascii_hexdigit()) => {
+                        u32::from_str_radix(hex, 16).ok()
+                    }
+                    _ if num.chars().all(|c| c.is_ascii_digit()) => num.parse().ok(),
+                    _ => None,
+                };
+                let valid = parsed.and_then(char::from_u32).is_some_and(|c| {
+                    is_char(c, false) || (self.v1_1 && matches!(c, '\u{1}'..='\u{1F}'))
+                });
+                if !valid {
+                    bail!(self, offset + i, "invalid character reference '&{reference};'");
+                }
+            } else if !matches!(reference, "lt" | "gt" | "amp" | "apos" | "quot") {
+                bail!(self, offset + i, "unknown entity '&{reference};'");
+            }
+            chars.nth(len - 1);
+        }
+        Ok(())
+    }
+
+    fn check_chars(&self, s: &str, offset: usize) -> Result<(), ParseError> {
+        s.char_indices().try_for_each(|(i, c)| self.check_char(c, offset + i))
+    }
+
+    fn check_char(&self, c: char, pos: usize) -> Result<(), ParseError> {
+        if !is_char(c, self.v1_1) {
+            bail!(self, pos, "character U+{:04X} is not allowed here", c as u32);
+        }
+        Ok(())
+    }
+
+    fn expect(&mut self, s: &str) -> Result<(), ParseError> {
+        if !self.rest().starts_with(s) {
+            bail!(self, self.pos, "expected '{s}'");
+        }
+        self.pos += s.len();
+        Ok(())
+    }
+
+    /// Skips whitespace and returns whether there was any.
+    fn skip_whitespace(&mut self) -> bool {
+        let rest = self.rest();
+        let len = rest.find(|c| !matches!(c, ' ' | '\t' | '\r' | '\n')).unwrap_or(rest.len());
+        self.pos += len;
+        len > 0
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.input[self.pos..]
+    }
+
+    /// Returns the position of the last returned event in the input.
+    pub(crate) fn event_pos(&self) -> usize {
+        self.event_pos
+    }
+
+    pub(crate) fn error_at(&self, pos: usize, msg: String) -> ParseError {
+        let before = &self.input[..pos];
+        let line_start = before.rfind('\n').map(|p| p + 1).unwrap_or(0);
+        ParseError {
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+            msg,
+        }
+    }
+}
+
+impl<'a> Iterator for Reader<'a> {
+    type Item = Result<Event<'a>, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        let out = self.next_event();
+        self.failed = out.is_err();
+        out.transpose()
+    }
+}
+
+/// Returns whether `c` may appear literally in a document. In XML 1.1
+/// documents, the restricted characters are only allowed as references.
+fn is_char(c: char, v1_1: bool) -> bool {
+    match c {
+        '\t' | '\n' | '\r' => true,
+        '\0'..='\u{1F}' | '\u{FFFE}' | '\u{FFFF}' => false,
+        _ => !v1_1 || !crate::needs_char_ref_1_1(c) || matches!(c, '\u{85}' | '\u{2028}'),
+    }
+}
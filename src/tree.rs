@@ -0,0 +1,337 @@
+//! Owned XML tree that can be inspected and modified before writing it.
+//!
+//! Unlike [`Document`], which is just a string, an [`Element`] is a tree of
+//! nodes. Create one with [`xml_tree!`][crate::xml_tree] (or parse it with
+//! [`Element::parse`]), modify it as you like, and write it with any
+//! [`Format`] via [`Element::to_document`] or [`Element::write_to`]. As
+//! attributes are stored as plain strings, boolean attributes like
+//! `hidden?={true}` get their name as value when building the tree, so
+//! [`Format::bool_attrs`] does not apply to them.
+//!
+//! ```
+//! use ogrim::{xml_tree, Format, tree::Node};
+//!
+//! let title = "Foxes";
+//! let mut tree = xml_tree!(
+//!     <feed>
+//!         <title>{title}</>
+//!         <entry id="1" />
+//!     </feed>
+//! );
+//!
+//! tree.set_attr("lang", "en");
+//! tree.children.insert(0, Node::Comment(" generated ".into()));
+//! tree.children.retain(|c| !matches!(c, Node::Element(e) if e.name == "entry"));
+//!
+//! let doc = tree.to_document(Format::terse());
+//! assert_eq!(doc.as_str(), concat!(
+//!     r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+//!     r#"<feed lang="en"><!-- generated --><title>Foxes</title></feed>"#,
+//! ));
+//! ```
+
+use std::fmt::{self, Write};
+
+use crate::{CharPolicy, Document, Encoding, Event, Format, ParseError, Reader, Version, is_name};
+
+
+
+/// An XML element with attributes and children.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Element {
+    pub name: String,
+    /// Attributes as `(name, value)` pairs in the order they are written.
+    /// Values are not escaped.
+    pub attrs: Vec<(String, String)>,
+    pub children: Vec<Node>,
+    /// Whether no whitespace is added inside this element when writing it
+    /// pretty printed, as set by `#[preserve_space]` in
+    /// [`xml_tree!`][crate::xml_tree]. Elements with `xml:space="preserve"`
+    /// are treated like this anyway.
+    pub preserve_space: bool,
+}
+
+/// A child of an [`Element`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Node {
+    Element(Element),
+    /// Text content, not escaped.
+    Text(String),
+    /// A comment, without `<!--` and `-->`.
+    Comment(String),
+    /// A processing instruction, e.g. `<?target data?>`.
+    Pi {
+        target: String,
+        data: String,
+    },
+}
+
+impl Element {
+    /// Creates an element without attributes and children.
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            attrs: Vec::new(),
+            children: Vec::new(),
+            preserve_space: false,
+        }
+    }
+
+    /// Parses the root element of the given document. Comments and
+    /// processing instructions outside of the root element are dropped.
+    pub fn parse(input: &str) -> Result<Self, ParseError> {
+        let mut builder = Builder::new();
+        for event in Reader::new(input) {
+            match event? {
+                Event::StartTag(name) => builder.open_tag(name),
+                Event::Attr { name, value } => builder.attr(name, &value.unescape()),
+                Event::Text(text) => builder.text(&text.unescape()),
+                Event::End(name) => builder.end_tag(name),
+                Event::Comment(c) => builder.push(Node::Comment(c.into())),
+                Event::Pi { target, data } => builder.push(Node::Pi {
+                    target: target.into(),
+                    data: data.into(),
+                }),
+            }
+        }
+        Ok(builder.finish())
+    }
+
+    /// Returns the value of the attribute with the given name.
+    pub fn attr(&self, name: &str) -> Option<&str> {
+        self.attrs.iter().find(|(n, _)| n == name).map(|(_, v)| v.as_str())
+    }
+
+    /// Sets the attribute with the given name, replacing the old value or
+    /// adding it as last attribute.
+    pub fn set_attr(&mut self, name: impl Into<String>, value: impl Into<String>) {
+        let name = name.into();
+        let value = value.into();
+        match self.attrs.iter_mut().find(|(n, _)| *n == name) {
+            Some((_, v)) => *v = value,
+            None => self.attrs.push((name, value)),
+        }
+    }
+
+    /// Removes the attribute with the given name and returns its value.
+    pub fn remove_attr(&mut self, name: &str) -> Option<String> {
+        let pos = self.attrs.iter().position(|(n, _)| n == name)?;
+        Some(self.attrs.remove(pos).1)
+    }
+
+    /// Returns an iterator over all child elements, skipping text and other
+    /// nodes.
+    pub fn elements(&self) -> impl Iterator<Item = &Element> {
+        self.children.iter().filter_map(|c| match c {
+            Node::Element(e) => Some(e),
+            _ => None,
+        })
+    }
+
+    /// Like [`Element::elements`], but returns mutable references.
+    pub fn elements_mut(&mut self) -> impl Iterator<Item = &mut Element> {
+        self.children.iter_mut().filter_map(|c| match c {
+            Node::Element(e) => Some(e),
+            _ => None,
+        })
+    }
+
+    /// Returns the first child element with the given name.
+    pub fn find(&self, name: &str) -> Option<&Element> {
+        self.elements().find(|e| e.name == name)
+    }
+
+    /// Like [`Element::find`], but returns a mutable reference.
+    pub fn find_mut(&mut self, name: &str) -> Option<&mut Element> {
+        self.elements_mut().find(|e| e.name == name)
+    }
+
+    /// Returns a new XML 1.0 document with this element as root element.
+    ///
+    /// # Panics
+    ///
+    /// Panics in the same cases as [`Element::write_to`].
+    pub fn to_document(&self, format: Format) -> Document {
+        let mut doc = Document::new(
+            Version::V1_0,
+            Encoding::Utf8,
+            None,
+            format,
+            CharPolicy::default(),
+            false,
+        );
+        self.write_to(&mut doc);
+        doc
+    }
+
+    /// Writes this element to the given document. This can also be used
+    /// inside `xml!` via `{|doc| elem.write_to(doc)}`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the element or any of its descendants has an invalid
+    /// element or attribute name, a comment containing `--` or ending with
+    /// `-`, or a processing instruction with an invalid target, the target
+    /// `xml` (in any case) or data containing `?>`.
+    pub fn write_to(&self, doc: &mut Document) {
+        if !is_name(&self.name) {
+            panic!("element name '{}' is not a valid XML name", self.name);
+        }
+
+        doc.open_tag(&self.name);
+        doc.attrs(self.attrs.iter().map(|(n, v)| (n, v)));
+        if self.preserve_space {
+            doc.preserve_space();
+        }
+        if self.children.is_empty() {
+            doc.close_empty_elem_tag();
+            return;
+        }
+
+        if self.children.iter().any(|c| matches!(c, Node::Text(_))) {
+            doc.mixed_content();
+        }
+        doc.close_start_tag();
+        for child in &self.children {
+            match child {
+                Node::Element(e) => e.write_to(doc),
+                Node::Text(text) => doc.text(text),
+                Node::Comment(comment) => doc.comment(comment),
+                Node::Pi { target, data } => doc.pi(target, data),
+            }
+        }
+        doc.end_tag(&self.name);
+    }
+}
+
+
+/// Builds an [`Element`]. This is what the `doc` in `{|doc| ...}` refers to
+/// inside [`xml_tree!`][crate::xml_tree]. Like with [`Document`], you can
+/// append to it with `xml!(doc, ...)`.
+///
+/// ```
+/// use ogrim::{xml, xml_tree, tree::Builder};
+///
+/// fn add_items(doc: &mut Builder) {
+///     for i in 0..2 {
+///         xml!(doc, <item>{i}</>);
+///     }
+/// }
+///
+/// let tree = xml_tree!(<items>{|doc| add_items(doc)}</>);
+/// assert_eq!(tree.elements().count(), 2);
+/// ```
+#[derive(Default)]
+pub struct Builder {
+    /// Elements that have been opened but not yet closed.
+    stack: Vec<Element>,
+    root: Option<Element>,
+}
+
+impl Builder {
+    #[doc(hidden)]
+    pub fn new() -> Self {
+        Self { stack: Vec::new(), root: None }
+    }
+
+    #[doc(hidden)]
+    pub fn finish(self) -> Element {
+        assert!(self.stack.is_empty());
+        self.root.expect("no root element")
+    }
+
+    #[doc(hidden)]
+    pub fn open_tag(&mut self, name: &str) {
+        self.stack.push(Element::new(name));
+    }
+
+    #[doc(hidden)]
+    pub fn attr(&mut self, name: &str, value: &dyn fmt::Display) {
+        self.current().attrs.push((name.into(), value.to_string()));
+    }
+
+    #[doc(hidden)]
+    pub fn typed_attr(&mut self, name: &str, value: &dyn crate::AttrValue) {
+        if !value.is_omitted() {
+            self.attr(name, &crate::attr::Formatted(value));
+        }
+    }
+
+    /// Boolean attributes always get their name as value, as trees have no
+    /// format.
+    #[doc(hidden)]
+    pub fn opt_attr(&mut self, name: &str, value: crate::__OptionalValue<'_>) {
+        match value {
+            crate::__OptionalValue::Omit => {}
+            crate::__OptionalValue::Flag => self.attr(name, &name),
+            crate::__OptionalValue::Value(v) => self.typed_attr(name, v),
+            crate::__OptionalValue::Display(v) => self.attr(name, v),
+        }
+    }
+
+    #[doc(hidden)]
+    pub fn attrs<I, N, V>(&mut self, attrs: I)
+    where
+        I: IntoIterator<Item = (N, V)>,
+        V: fmt::Display,
+        N: fmt::Display,
+    {
+        for (name, value) in attrs {
+            let name = name.to_string();
+            if !is_name(&name) {
+                panic!("attribute name '{name}' is not a valid XML name");
+            }
+            self.current().attrs.push((name, value.to_string()));
+        }
+    }
+
+    #[doc(hidden)]
+    pub fn preserve_space(&mut self) {
+        self.current().preserve_space = true;
+    }
+
+    /// Trees have no formatting, so this does nothing.
+    #[doc(hidden)]
+    pub fn mixed_content(&mut self) {}
+
+    #[doc(hidden)]
+    pub fn close_start_tag(&mut self) {}
+
+    #[doc(hidden)]
+    pub fn close_empty_elem_tag(&mut self) {
+        self.close();
+    }
+
+    #[doc(hidden)]
+    pub fn end_tag(&mut self, _name: &str) {
+        self.close();
+    }
+
+    #[doc(hidden)]
+    pub fn text(&mut self, text: &dyn fmt::Display) {
+        let children = &mut self.current().children;
+        match children.last_mut() {
+            Some(Node::Text(s)) => write!(s, "{text}").unwrap(),
+            _ => children.push(Node::Text(text.to_string())),
+        }
+    }
+
+    fn push(&mut self, node: Node) {
+        // Comments and PIs outside the root element are dropped.
+        if let Some(parent) = self.stack.last_mut() {
+            parent.children.push(node);
+        }
+    }
+
+    fn close(&mut self) {
+        let elem = self.stack.pop().expect("unbalanced tags");
+        match self.stack.last_mut() {
+            Some(parent) => parent.children.push(Node::Element(elem)),
+            None => self.root = Some(elem),
+        }
+    }
+
+    fn current(&mut self) -> &mut Element {
+        self.stack.last_mut().expect("no open element")
+    }
+}
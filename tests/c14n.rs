@@ -0,0 +1,84 @@
+//! Examples from the [Canonical XML 1.0 spec][spec], section 3. Parts
+//! depending on the DTD (default attributes and entities) are removed.
+//!
+//! [spec]: https://www.w3.org/TR/2001/REC-xml-c14n-20010315#Examples
+
+use ogrim::C14n;
+
+
+/// 3.1 PIs, comments, and outside of document element
+#[test]
+fn pis_comments_and_outside_of_document_element() {
+    let input = "<?xml version=\"1.0\"?>\n\n\
+        <?xml-stylesheet   href=\"doc.xsl\"\n   type=\"text/xsl\"   ?>\n\n\
+        <!DOCTYPE doc SYSTEM \"doc.dtd\">\n\n\
+        <doc>Hello, world!<!-- Comment 1 --></doc>\n\n\
+        <?pi-without-data     ?>\n\n<!-- Comment 2 -->\n\n<!-- Comment 3 -->\n";
+    assert_eq!(C14n::inclusive().canonicalize(input).unwrap(),
+        "<?xml-stylesheet href=\"doc.xsl\"\n   type=\"text/xsl\"   ?>\n\
+        <doc>Hello, world!</doc>\n<?pi-without-data?>");
+    assert_eq!(C14n::inclusive().with_comments(true).canonicalize(input).unwrap(),
+        "<?xml-stylesheet href=\"doc.xsl\"\n   type=\"text/xsl\"   ?>\n\
+        <doc>Hello, world!<!-- Comment 1 --></doc>\n<?pi-without-data?>\n\
+        <!-- Comment 2 -->\n<!-- Comment 3 -->");
+}
+
+/// 3.2 Whitespace in document content
+#[test]
+fn whitespace_in_document_content() {
+    let input = "<doc>\n   <clean>   </clean>\n   <dirty>   A   B   </dirty>\n   \
+        <mixed>\n      A\n      <clean>   </clean>\n      B\n      \
+        <dirty>   A   B   </dirty>\n      C\n   </mixed>\n</doc>";
+    assert_eq!(C14n::inclusive().canonicalize(input).unwrap(), input);
+}
+
+/// 3.3 Start and end tags
+#[test]
+fn start_and_end_tags() {
+    let input = r#"<!DOCTYPE doc [<!ATTLIST e9 attr CDATA "default">]>
+<doc>
+   <e1   />
+   <e2   ></e2>
+   <e3   name = "elem3"   id="elem3"   />
+   <e4   name="elem4"   id="elem4"   ></e4>
+   <e5 a:attr="out" b:attr="sorted" attr2="all" attr="I'm"
+      xmlns:b="http://www.ietf.org"
+      xmlns:a="http://www.w3.org"
+      xmlns="http://example.org"/>
+   <e6 xmlns="" xmlns:a="http://www.w3.org">
+      <e7 xmlns="http://www.ietf.org">
+         <e8 xmlns="" xmlns:a="http://www.w3.org">
+            <e9 xmlns="" xmlns:a="http://www.ietf.org"/>
+         </e8>
+      </e7>
+   </e6>
+</doc>"#;
+    assert_eq!(C14n::inclusive().canonicalize(input).unwrap(), r#"<doc>
+   <e1></e1>
+   <e2></e2>
+   <e3 id="elem3" name="elem3"></e3>
+   <e4 id="elem4" name="elem4"></e4>
+   <e5 xmlns="http://example.org" xmlns:a="http://www.w3.org" xmlns:b="http://www.ietf.org" attr="I'm" attr2="all" b:attr="sorted" a:attr="out"></e5>
+   <e6 xmlns:a="http://www.w3.org">
+      <e7 xmlns="http://www.ietf.org">
+         <e8 xmlns="">
+            <e9 xmlns:a="http://www.ietf.org"></e9>
+         </e8>
+      </e7>
+   </e6>
+</doc>"#);
+}
+
+/// 3.4 Character modifications and character references
+#[test]
+fn character_modifications_and_character_references() {
+    let input = "<doc>\r\n   <text>First line&#x0d;&#10;Second line</text>\r\n   \
+        <value>&#x32;</value>\r\n   <compute><![CDATA[value>\"0\" && value<\"10\" ?\"valid\":\"error\"]]></compute>\r\n   \
+        <compute expr='value>\"0\" &amp;&amp; value&lt;\"10\" ?\"valid\":\"error\"'>valid</compute>\r\n   \
+        <norm attr=' &apos;   &#x20;&#13;&#xa;&#9;   &apos; '/>\r\n</doc>";
+    assert_eq!(C14n::inclusive().canonicalize(input).unwrap(), "<doc>\n   \
+        <text>First line&#xD;\nSecond line</text>\n   <value>2</value>\n   \
+        <compute>value&gt;\"0\" &amp;&amp; value&lt;\"10\" ?\"valid\":\"error\"</compute>\n   \
+        <compute expr=\"value>&quot;0&quot; &amp;&amp; value&lt;&quot;10&quot; ?&quot;valid&quot;:&quot;error&quot;\">valid</compute>\n   \
+        <norm attr=\" '    &#xD;&#xA;&#x9;   ' \"></norm>\n</doc>");
+}